@@ -0,0 +1,1247 @@
+use crate::*;
+
+// one method per SysNo: allocate the result variable, append the syscall
+// and keep fds and file objects up to date
+impl Program {
+    // index of a Str variable holding path, created if there is none yet
+    pub fn path_index(&mut self, path: &str) -> i64 {
+        if let Some(fobj) = self.find_file(path) {
+            let index = fobj.fd_index;
+            if let Ok(v) = self.try_get_variable(index) {
+                if *v.var_type == VariableType::Str(String::from(path)) {
+                    return index;
+                }
+            }
+        }
+        let existing = self
+            .variables
+            .iter()
+            .position(|v| *v.var_type == VariableType::Str(String::from(path)));
+        match existing {
+            Some(index) => index as i64,
+            None => self.create_str(path),
+        }
+    }
+
+    pub fn find_file(&self, path: &str) -> Option<&FileObject> {
+        self.avail_files.iter().find(|x| x.rel_path == path)
+    }
+
+    // drop every file object at path
+    pub fn forget_file(&mut self, path: &str) {
+        self.avail_files.retain(|x| x.rel_path != path);
+        self.avail_dirs.retain(|x| x.rel_path != path);
+        self.avail_non_dirs.retain(|x| x.rel_path != path);
+    }
+
+    // register a new file object at path unless one already exists
+    fn learn_file(&mut self, path: &str, ftype: FileType) -> Result<(), HdError> {
+        if self.find_file(path).is_some() {
+            return Ok(());
+        }
+        let index = self.path_index(path);
+        self.try_add_file(FileObject::new(path, ftype, index), index)
+    }
+
+    // like learn_file, recording the mode a newly created file ends up with
+    fn learn_created(&mut self, path: &str, ftype: FileType, mode: i64) -> Result<(), HdError> {
+        if self.find_file(path).is_some() {
+            return Ok(());
+        }
+        self.learn_file(path, ftype)?;
+        if let Some(mask) = self.current_umask() {
+            self.update_meta(path, |m| m.mode = Some(mode & 0o7777 & !mask));
+        }
+        Ok(())
+    }
+
+    // apply f to the metadata of every file object at path
+    pub fn update_meta<F: Fn(&mut FileMeta)>(&mut self, path: &str, f: F) {
+        let lists = [
+            &mut self.avail_files,
+            &mut self.avail_dirs,
+            &mut self.avail_non_dirs,
+        ];
+        for list in lists {
+            for fobj in list.iter_mut().filter(|x| x.rel_path == path) {
+                f(&mut fobj.meta);
+            }
+        }
+    }
+
+    // like update_meta, but skips symlinks whose target is not tracked
+    fn update_meta_follow<F: Fn(&mut FileMeta)>(&mut self, path: &str, f: F) {
+        if self.find_file(path).map(|x| x.ftype) != Some(FileType::Symlink) {
+            self.update_meta(path, f);
+        }
+    }
+
+    // mask set by the last umask syscall, None if the program never set one
+    pub fn current_umask(&self) -> Option<i64> {
+        self.syscalls
+            .iter()
+            .rev()
+            .find(|x| x.nr == SysNo::Umask)
+            .and_then(|x| x.args.first())
+            .and_then(|x| x.value)
+    }
+
+    // rewrite file objects from the first to the second path all at once
+    fn rewrite_files(&mut self, moved: Vec<(String, String)>) {
+        let moved: Vec<(String, String, i64)> = moved
+            .into_iter()
+            .map(|(from, to)| {
+                let index = self.path_index(&to);
+                (from, to, index)
+            })
+            .collect();
+        let lists = [
+            &mut self.avail_files,
+            &mut self.avail_dirs,
+            &mut self.avail_non_dirs,
+        ];
+        for list in lists {
+            for fobj in list.iter_mut() {
+                if let Some((_, to, index)) = moved.iter().find(|x| x.0 == fobj.rel_path) {
+                    fobj.rel_path = to.clone();
+                    fobj.fd_index = *index;
+                }
+            }
+        }
+    }
+
+    // move the file object at old (and everything below it) to new
+    fn move_file(&mut self, old: &str, new: &str) {
+        if old == new {
+            return;
+        }
+        let moved: Vec<(String, String)> = self
+            .avail_files
+            .iter()
+            .filter_map(|x| relocate(&x.rel_path, old, new).map(|to| (x.rel_path.clone(), to)))
+            .collect();
+        // whatever was at the destination is replaced
+        for (_, to) in moved.iter() {
+            self.forget_file(to);
+        }
+        self.rewrite_files(moved);
+    }
+
+    // swap the file objects at a and b (and everything below them)
+    fn exchange_files(&mut self, a: &str, b: &str) {
+        let moved: Vec<(String, String)> = self
+            .avail_files
+            .iter()
+            .filter_map(|x| {
+                relocate(&x.rel_path, a, b)
+                    .or_else(|| relocate(&x.rel_path, b, a))
+                    .map(|to| (x.rel_path.clone(), to))
+            })
+            .collect();
+        self.rewrite_files(moved);
+    }
+
+    // path an fd variable was opened with, following the *at and dup families
+    pub fn fd_path(&self, fd: i64) -> Option<String> {
+        self.fd_path_before(fd, self.syscalls.len())
+    }
+
+    // same as fd_path but only looking at the syscalls before end
+    pub fn fd_path_before(&self, fd: i64, end: usize) -> Option<String> {
+        // dup2 and dup3 also redefine their newfd argument
+        let (pos, syscall) = self.syscalls[..end.min(self.syscalls.len())]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, x)| match x.nr {
+                SysNo::Open | SysNo::Openat | SysNo::Dup => x.ret_index == fd,
+                SysNo::Dup2 | SysNo::Dup3 => {
+                    x.ret_index == fd || arg_var(x.args.get(1)) == Some(fd)
+                }
+                _ => false,
+            })?;
+        match syscall.nr {
+            SysNo::Open => self.arg_str(syscall.args.first()),
+            SysNo::Openat => {
+                let path = self.arg_str(syscall.args.get(1))?;
+                let dirfd = arg_dirfd(syscall.args.first())?;
+                self.resolve_at_before(dirfd, &path, pos)
+            }
+            _ => self.fd_path_before(arg_var(syscall.args.first())?, pos),
+        }
+    }
+
+    // every fd variable holding the same descriptor number as fd, fd included
+    pub fn fd_aliases(&self, fd: i64) -> Vec<i64> {
+        self.fd_aliases_before(fd, self.syscalls.len())
+    }
+
+    // same as fd_aliases but only looking at the syscalls before end
+    pub fn fd_aliases_before(&self, fd: i64, end: usize) -> Vec<i64> {
+        let pairs: Vec<(i64, i64)> = self.syscalls[..end.min(self.syscalls.len())]
+            .iter()
+            .filter(|x| (x.nr == SysNo::Dup2 || x.nr == SysNo::Dup3) && x.ret_index != -1)
+            .filter_map(|x| match x.args.get(1) {
+                Some(a) if a.is_variable => a.index.map(|i| (x.ret_index, i as i64)),
+                _ => None,
+            })
+            .collect();
+        let mut aliases = vec![fd];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (a, b) in pairs.iter() {
+                if aliases.contains(a) != aliases.contains(b) {
+                    aliases.push(if aliases.contains(a) { *b } else { *a });
+                    changed = true;
+                }
+            }
+        }
+        aliases
+    }
+
+    // the string of a Str variable passed as arg
+    pub fn arg_str(&self, arg: Option<&Arg>) -> Option<String> {
+        let index = arg_var(arg)?;
+        match &*self.variables.get(index as usize)?.var_type {
+            VariableType::Str(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    // path relative to the working directory of path relative to dirfd,
+    // None if the path dirfd was opened with is not known
+    pub fn resolve_at(&self, dirfd: i64, path: &str) -> Option<String> {
+        self.resolve_at_before(dirfd, path, self.syscalls.len())
+    }
+
+    // same as resolve_at with dirfd as it was before syscall end
+    pub fn resolve_at_before(&self, dirfd: i64, path: &str, end: usize) -> Option<String> {
+        if dirfd == AT_FDCWD || path.starts_with('/') {
+            return Some(String::from(path));
+        }
+        let dir = self.fd_path_before(dirfd, end)?;
+        Some(format!("{}/{}", dir, path))
+    }
+
+    // where the file at path right after syscall pos lives at the end of the
+    // program, None if it was removed or replaced
+    pub fn path_after(&self, path: &str, pos: usize) -> Option<String> {
+        let mut path = String::from(path);
+        for (j, s) in self.syscalls.iter().enumerate().skip(pos + 1) {
+            let at = |dirfd: usize, name: usize| -> Option<String> {
+                let name = self.arg_str(s.args.get(name))?;
+                self.resolve_at_before(arg_dirfd(s.args.get(dirfd))?, &name, j)
+            };
+            let (old, new, exchange, noreplace) = match s.nr {
+                SysNo::Rename => (
+                    self.arg_str(s.args.first()),
+                    self.arg_str(s.args.get(1)),
+                    false,
+                    false,
+                ),
+                SysNo::Renameat2 => {
+                    let flags = s.args.get(4).and_then(|x| x.value).unwrap_or(0);
+                    (
+                        at(0, 1),
+                        at(2, 3),
+                        flags & RENAME_EXCHANGE != 0,
+                        flags & RENAME_NOREPLACE != 0,
+                    )
+                }
+                SysNo::Unlink | SysNo::Rmdir => {
+                    if self.arg_str(s.args.first()).as_deref() == Some(&path) {
+                        return None;
+                    }
+                    continue;
+                }
+                SysNo::Unlinkat => {
+                    if at(0, 1).as_deref() == Some(&path) {
+                        return None;
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            let (old, new) = match (old, new) {
+                (Some(o), Some(n)) if o != n => (o, n),
+                _ => continue,
+            };
+            if let Some(moved) = relocate(&path, &old, &new) {
+                path = moved;
+            } else if exchange {
+                if let Some(moved) = relocate(&path, &new, &old) {
+                    path = moved;
+                }
+            } else if path == new && !noreplace {
+                // overwritten by the rename, RENAME_NOREPLACE fails instead
+                return None;
+            }
+        }
+        Some(path)
+    }
+
+    // values usable as the dirfd of the *at family
+    pub fn dirfd_choices(&self) -> Vec<i64> {
+        let mut choices = self.active_dir_fds.clone();
+        choices.push(AT_FDCWD);
+        choices
+    }
+
+    // AT_FDCWD is passed as a value, anything else as an fd variable
+    fn dirfd_arg(builder: SyscallBuilder, dirfd: i64) -> SyscallBuilder {
+        if dirfd == AT_FDCWD {
+            builder.value(dirfd)
+        } else {
+            builder.var(dirfd)
+        }
+    }
+
+    fn push_checked(&mut self, builder: SyscallBuilder) -> Result<(), HdError> {
+        let syscall = builder.build(self)?;
+        self.add_syscall(syscall);
+        Ok(())
+    }
+
+    // push the syscall storing its result into a new variable. the variable
+    // only becomes an active fd once the syscall is in, a refused syscall
+    // leaves no variable behind
+    fn push_with_ret(
+        &mut self,
+        builder: SyscallBuilder,
+        var_type: VariableType,
+        kind: FileType,
+    ) -> Result<i64, HdError> {
+        let ret = self.variables.len() as i64;
+        let name = format!("v{}", ret);
+        self.variables.push(Variable::new(&name, var_type, kind));
+        if let Err(e) = self.push_checked(builder.ret(ret)) {
+            self.variables.pop();
+            return Err(e);
+        }
+        if kind.is_fd_kind() {
+            self.try_add_fd(ret)?;
+        }
+        Ok(ret)
+    }
+
+    // returns the index of the fd variable
+    pub fn open(&mut self, path: &str, flags: i64, mode: i64) -> Result<i64, HdError> {
+        let kind = match self.find_file(path) {
+            Some(fobj) => fobj.ftype,
+            None if flags & O_DIRECTORY != 0 => FileType::Dir,
+            None => FileType::File,
+        };
+        let path_index = self.path_index(path);
+        let fd = self.push_with_ret(
+            SyscallBuilder::new(SysNo::Open)
+                .var(path_index)
+                .value(flags)
+                .value(mode),
+            VariableType::Long(-1),
+            kind,
+        )?;
+        if flags & O_CREAT != 0 {
+            self.learn_created(path, kind, mode)?;
+        }
+        Ok(fd)
+    }
+
+    pub fn read(&mut self, fd: i64, buf: i64, count: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Read)
+                .var(fd)
+                .var(buf)
+                .value(count),
+        )
+    }
+
+    pub fn write(&mut self, fd: i64, buf: i64, count: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Write)
+                .var(fd)
+                .var(buf)
+                .value(count),
+        )
+    }
+
+    pub fn lseek(&mut self, fd: i64, offset: i64, whence: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Lseek)
+                .var(fd)
+                .value(offset)
+                .value(whence),
+        )
+    }
+
+    pub fn getdents(&mut self, fd: i64, buf: i64, count: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Getdents)
+                .var(fd)
+                .var(buf)
+                .value(count),
+        )
+    }
+
+    pub fn pread(&mut self, fd: i64, buf: i64, count: i64, offset: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Pread)
+                .var(fd)
+                .var(buf)
+                .value(count)
+                .value(offset),
+        )
+    }
+
+    pub fn pwrite(&mut self, fd: i64, buf: i64, count: i64, offset: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Pwrite)
+                .var(fd)
+                .var(buf)
+                .value(count)
+                .value(offset),
+        )
+    }
+
+    // buf receives the struct stat
+    pub fn fstat(&mut self, fd: i64, buf: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Fstat).var(fd).var(buf))
+    }
+
+    pub fn stat(&mut self, path: &str, buf: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(SyscallBuilder::new(SysNo::Stat).var(path_index).var(buf))
+    }
+
+    pub fn lstat(&mut self, path: &str, buf: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(SyscallBuilder::new(SysNo::Lstat).var(path_index).var(buf))
+    }
+
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), HdError> {
+        let old_index = self.path_index(old);
+        let new_index = self.path_index(new);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Rename)
+                .var(old_index)
+                .var(new_index),
+        )?;
+        self.move_file(old, new);
+        Ok(())
+    }
+
+    pub fn fsync(&mut self, fd: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Fsync).var(fd))
+    }
+
+    pub fn fdatasync(&mut self, fd: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Fdatasync).var(fd))
+    }
+
+    pub fn syncfs(&mut self, fd: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Syncfs).var(fd))
+    }
+
+    // copies from the file offset of in_fd, the offset pointer is NULL
+    pub fn sendfile(&mut self, out_fd: i64, in_fd: i64, count: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Sendfile)
+                .var(out_fd)
+                .var(in_fd)
+                .value(0)
+                .value(count),
+        )
+    }
+
+    pub fn access(&mut self, path: &str, mode: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Access)
+                .var(path_index)
+                .value(mode),
+        )
+    }
+
+    pub fn ftruncate(&mut self, fd: i64, length: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Ftruncate).var(fd).value(length))
+    }
+
+    pub fn truncate(&mut self, path: &str, length: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Truncate)
+                .var(path_index)
+                .value(length),
+        )
+    }
+
+    pub fn mkdir(&mut self, path: &str, mode: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Mkdir)
+                .var(path_index)
+                .value(mode),
+        )?;
+        self.learn_created(path, FileType::Dir, mode)
+    }
+
+    pub fn rmdir(&mut self, path: &str) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(SyscallBuilder::new(SysNo::Rmdir).var(path_index))?;
+        self.forget_file(path);
+        Ok(())
+    }
+
+    pub fn link(&mut self, old: &str, new: &str) -> Result<(), HdError> {
+        let ftype = self.find_file(old).map_or(FileType::File, |x| x.ftype);
+        let old_index = self.path_index(old);
+        let new_index = self.path_index(new);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Link)
+                .var(old_index)
+                .var(new_index),
+        )?;
+        self.learn_file(new, ftype)
+    }
+
+    pub fn unlink(&mut self, path: &str) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(SyscallBuilder::new(SysNo::Unlink).var(path_index))?;
+        self.forget_file(path);
+        Ok(())
+    }
+
+    // creates linkpath pointing at target
+    pub fn symlink(&mut self, target: &str, linkpath: &str) -> Result<(), HdError> {
+        let target_index = self.path_index(target);
+        let link_index = self.path_index(linkpath);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Symlink)
+                .var(target_index)
+                .var(link_index),
+        )?;
+        self.learn_file(linkpath, FileType::Symlink)
+    }
+
+    // flags is one of XATTR_FLAGS
+    pub fn setxattr(
+        &mut self,
+        path: &str,
+        name: &str,
+        value: &[u8],
+        flags: i64,
+    ) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.set_xattr_on(SysNo::Setxattr, path_index, name, value, flags)
+    }
+
+    // does not follow a symlink at path
+    pub fn lsetxattr(
+        &mut self,
+        path: &str,
+        name: &str,
+        value: &[u8],
+        flags: i64,
+    ) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.set_xattr_on(SysNo::Lsetxattr, path_index, name, value, flags)
+    }
+
+    pub fn fsetxattr(
+        &mut self,
+        fd: i64,
+        name: &str,
+        value: &[u8],
+        flags: i64,
+    ) -> Result<(), HdError> {
+        self.set_xattr_on(SysNo::Fsetxattr, fd, name, value, flags)
+    }
+
+    pub fn getxattr(&mut self, path: &str, name: &str, buf: i64, size: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.get_xattr_on(SysNo::Getxattr, path_index, name, buf, size)
+    }
+
+    pub fn lgetxattr(
+        &mut self,
+        path: &str,
+        name: &str,
+        buf: i64,
+        size: i64,
+    ) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.get_xattr_on(SysNo::Lgetxattr, path_index, name, buf, size)
+    }
+
+    pub fn fgetxattr(&mut self, fd: i64, name: &str, buf: i64, size: i64) -> Result<(), HdError> {
+        self.get_xattr_on(SysNo::Fgetxattr, fd, name, buf, size)
+    }
+
+    pub fn removexattr(&mut self, path: &str, name: &str) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.remove_xattr_on(SysNo::Removexattr, path_index, name)
+    }
+
+    pub fn lremovexattr(&mut self, path: &str, name: &str) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.remove_xattr_on(SysNo::Lremovexattr, path_index, name)
+    }
+
+    pub fn fremovexattr(&mut self, fd: i64, name: &str) -> Result<(), HdError> {
+        self.remove_xattr_on(SysNo::Fremovexattr, fd, name)
+    }
+
+    pub fn listxattr(&mut self, path: &str, buf: i64, size: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.list_xattr_on(SysNo::Listxattr, path_index, buf, size)
+    }
+
+    pub fn llistxattr(&mut self, path: &str, buf: i64, size: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.list_xattr_on(SysNo::Llistxattr, path_index, buf, size)
+    }
+
+    pub fn flistxattr(&mut self, fd: i64, buf: i64, size: i64) -> Result<(), HdError> {
+        self.list_xattr_on(SysNo::Flistxattr, fd, buf, size)
+    }
+
+    // the value is stored in its own byte buffer so that it may hold any bytes
+    fn set_xattr_on(
+        &mut self,
+        nr: SysNo,
+        target: i64,
+        name: &str,
+        value: &[u8],
+        flags: i64,
+    ) -> Result<(), HdError> {
+        let name_index = self.create_str(name);
+        let value_index = self.create_variable(VariableType::UCharPtr(
+            Some(value.to_vec()),
+            value.len() as u32,
+        ));
+        self.push_checked(
+            SyscallBuilder::new(nr)
+                .var(target)
+                .var(name_index)
+                .var(value_index)
+                .value(value.len() as i64)
+                .value(flags),
+        )
+    }
+
+    fn get_xattr_on(
+        &mut self,
+        nr: SysNo,
+        target: i64,
+        name: &str,
+        buf: i64,
+        size: i64,
+    ) -> Result<(), HdError> {
+        let name_index = self.create_str(name);
+        self.push_checked(
+            SyscallBuilder::new(nr)
+                .var(target)
+                .var(name_index)
+                .var(buf)
+                .value(size),
+        )
+    }
+
+    fn remove_xattr_on(&mut self, nr: SysNo, target: i64, name: &str) -> Result<(), HdError> {
+        let name_index = self.create_str(name);
+        self.push_checked(SyscallBuilder::new(nr).var(target).var(name_index))
+    }
+
+    fn list_xattr_on(
+        &mut self,
+        nr: SysNo,
+        target: i64,
+        buf: i64,
+        size: i64,
+    ) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(nr).var(target).var(buf).value(size))
+    }
+
+    // targets for the l*xattr family
+    pub fn avail_symlinks(&self) -> Vec<&FileObject> {
+        self.avail_non_dirs
+            .iter()
+            .filter(|x| x.ftype == FileType::Symlink)
+            .collect()
+    }
+
+    // dirfd is an index from active_dir_fds or AT_FDCWD
+    pub fn openat(
+        &mut self,
+        dirfd: i64,
+        path: &str,
+        flags: i64,
+        mode: i64,
+    ) -> Result<i64, HdError> {
+        let full = self.resolve_at(dirfd, path);
+        let kind = match full.as_deref().and_then(|x| self.find_file(x)) {
+            Some(fobj) => fobj.ftype,
+            None if flags & O_DIRECTORY != 0 => FileType::Dir,
+            None => FileType::File,
+        };
+        let path_index = self.path_index(path);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Openat), dirfd);
+        let builder = builder.var(path_index).value(flags).value(mode);
+        let fd = self.push_with_ret(builder, VariableType::Long(-1), kind)?;
+        match full {
+            Some(full) if flags & O_CREAT != 0 => self.learn_created(&full, kind, mode)?,
+            _ => {}
+        }
+        Ok(fd)
+    }
+
+    pub fn mkdirat(&mut self, dirfd: i64, path: &str, mode: i64) -> Result<(), HdError> {
+        let full = self.resolve_at(dirfd, path);
+        let path_index = self.path_index(path);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Mkdirat), dirfd);
+        self.push_checked(builder.var(path_index).value(mode))?;
+        match full {
+            Some(full) => self.learn_created(&full, FileType::Dir, mode),
+            None => Ok(()),
+        }
+    }
+
+    // flags may contain AT_REMOVEDIR
+    pub fn unlinkat(&mut self, dirfd: i64, path: &str, flags: i64) -> Result<(), HdError> {
+        let full = self.resolve_at(dirfd, path);
+        let path_index = self.path_index(path);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Unlinkat), dirfd);
+        self.push_checked(builder.var(path_index).value(flags))?;
+        if let Some(full) = full {
+            self.forget_file(&full);
+        }
+        Ok(())
+    }
+
+    // flags are RENAME_NOREPLACE, RENAME_EXCHANGE or RENAME_WHITEOUT
+    pub fn renameat2(
+        &mut self,
+        olddirfd: i64,
+        old: &str,
+        newdirfd: i64,
+        new: &str,
+        flags: i64,
+    ) -> Result<(), HdError> {
+        let old_full = self.resolve_at(olddirfd, old);
+        let new_full = self.resolve_at(newdirfd, new);
+        let old_index = self.path_index(old);
+        let new_index = self.path_index(new);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Renameat2), olddirfd);
+        let builder = Self::dirfd_arg(builder.var(old_index), newdirfd);
+        let noreplace = flags & RENAME_NOREPLACE != 0;
+        let exists = new_full
+            .as_deref()
+            .and_then(|x| self.find_file(x))
+            .is_some();
+        self.push_checked(builder.var(new_index).value(flags))?;
+        let (old_full, new_full) = match (old_full, new_full) {
+            (Some(o), Some(n)) => (o, n),
+            _ => return Ok(()),
+        };
+        // with RENAME_NOREPLACE onto a name in use it fails with EEXIST
+        if flags & RENAME_EXCHANGE != 0 {
+            self.exchange_files(&old_full, &new_full);
+        } else if !(noreplace && exists) {
+            let ftype = self.find_file(&old_full).map(|x| x.ftype);
+            self.move_file(&old_full, &new_full);
+            // a whiteout device takes the place of the old name
+            if flags & RENAME_WHITEOUT != 0 && ftype.is_some() {
+                self.learn_file(&old_full, FileType::CharDev)?;
+            }
+        }
+        Ok(())
+    }
+
+    // flags may contain AT_SYMLINK_FOLLOW
+    pub fn linkat(
+        &mut self,
+        olddirfd: i64,
+        old: &str,
+        newdirfd: i64,
+        new: &str,
+        flags: i64,
+    ) -> Result<(), HdError> {
+        let old_full = self.resolve_at(olddirfd, old);
+        let new_full = self.resolve_at(newdirfd, new);
+        let ftype = old_full
+            .as_deref()
+            .and_then(|x| self.find_file(x))
+            .map_or(FileType::File, |x| x.ftype);
+        let old_index = self.path_index(old);
+        let new_index = self.path_index(new);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Linkat), olddirfd);
+        let builder = Self::dirfd_arg(builder.var(old_index), newdirfd);
+        self.push_checked(builder.var(new_index).value(flags))?;
+        match new_full {
+            Some(new_full) => self.learn_file(&new_full, ftype),
+            None => Ok(()),
+        }
+    }
+
+    pub fn symlinkat(
+        &mut self,
+        target: &str,
+        newdirfd: i64,
+        linkpath: &str,
+    ) -> Result<(), HdError> {
+        let full = self.resolve_at(newdirfd, linkpath);
+        let target_index = self.path_index(target);
+        let link_index = self.path_index(linkpath);
+        let builder = SyscallBuilder::new(SysNo::Symlinkat).var(target_index);
+        let builder = Self::dirfd_arg(builder, newdirfd);
+        self.push_checked(builder.var(link_index))?;
+        match full {
+            Some(full) => self.learn_file(&full, FileType::Symlink),
+            None => Ok(()),
+        }
+    }
+
+    // flags may contain AT_SYMLINK_NOFOLLOW or AT_EMPTY_PATH
+    pub fn fstatat(&mut self, dirfd: i64, path: &str, buf: i64, flags: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Fstatat), dirfd);
+        self.push_checked(builder.var(path_index).var(buf).value(flags))
+    }
+
+    // returns the index of the mapping base variable
+    pub fn mmap(
+        &mut self,
+        fd: i64,
+        length: i64,
+        prot: i64,
+        flags: i64,
+        offset: i64,
+    ) -> Result<i64, HdError> {
+        let base = self.push_with_ret(
+            SyscallBuilder::new(SysNo::Mmap)
+                .value(0)
+                .value(length)
+                .value(prot)
+                .value(flags)
+                .var(fd)
+                .value(offset),
+            VariableType::MmapBase,
+            FileType::Mmap,
+        )?;
+        self.add_map_base(base, length);
+        Ok(base)
+    }
+
+    pub fn munmap(&mut self, base: i64, length: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Munmap).var(base).value(length))?;
+        // the base address itself is gone even if only a prefix was unmapped
+        self.try_mark_base_unmapped(base)
+    }
+
+    pub fn msync(&mut self, base: i64, length: i64, flags: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Msync)
+                .var(base)
+                .value(length)
+                .value(flags),
+        )
+    }
+
+    pub fn madvise(&mut self, base: i64, length: i64, advice: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Madvise)
+                .var(base)
+                .value(length)
+                .value(advice),
+        )
+    }
+
+    // returns the index of the new mapping base variable
+    pub fn mremap(
+        &mut self,
+        base: i64,
+        old_size: i64,
+        new_size: i64,
+        flags: i64,
+    ) -> Result<i64, HdError> {
+        if self.map_size(base).is_none() {
+            return Err(HdError::DanglingIndex(base));
+        }
+        let new_base = self.push_with_ret(
+            SyscallBuilder::new(SysNo::Mremap)
+                .var(base)
+                .value(old_size)
+                .value(new_size)
+                .value(flags)
+                .value(0),
+            VariableType::MmapBase,
+            FileType::Mmap,
+        )?;
+        self.try_mark_base_unmapped(base)?;
+        self.add_map_base(new_base, new_size);
+        Ok(new_base)
+    }
+
+    // copy length bytes of buf to offset within the mapping
+    pub fn memcpy_to_map(
+        &mut self,
+        base: i64,
+        offset: i64,
+        buf: i64,
+        length: i64,
+    ) -> Result<(), HdError> {
+        let size = self.map_size(base).ok_or(HdError::DanglingIndex(base))?;
+        if offset < 0 || length < 0 || offset.checked_add(length).is_none_or(|end| end > size) {
+            return Err(HdError::MapOutOfBounds {
+                index: base,
+                offset,
+                length,
+                size,
+            });
+        }
+        self.push_checked(
+            SyscallBuilder::new(SysNo::MemcpyToMap)
+                .var(base)
+                .value(offset)
+                .var(buf)
+                .value(length),
+        )
+    }
+
+    // marker the C backend turns into the crash hook for id
+    pub fn crash_point(&mut self, id: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::CrashPoint).value(id))
+    }
+
+    // mode is one of FALLOC_MODES
+    pub fn fallocate(
+        &mut self,
+        fd: i64,
+        mode: i64,
+        offset: i64,
+        length: i64,
+    ) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Fallocate)
+                .var(fd)
+                .value(mode)
+                .value(offset)
+                .value(length),
+        )
+    }
+
+    // copies between the file offsets, both offset pointers are NULL
+    pub fn copy_file_range(
+        &mut self,
+        fd_in: i64,
+        fd_out: i64,
+        length: i64,
+        flags: i64,
+    ) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::CopyFileRange)
+                .var(fd_in)
+                .value(0)
+                .var(fd_out)
+                .value(0)
+                .value(length)
+                .value(flags),
+        )
+    }
+
+    pub fn sync_file_range(
+        &mut self,
+        fd: i64,
+        offset: i64,
+        nbytes: i64,
+        flags: i64,
+    ) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::SyncFileRange)
+                .var(fd)
+                .value(offset)
+                .value(nbytes)
+                .value(flags),
+        )
+    }
+
+    pub fn fadvise64(
+        &mut self,
+        fd: i64,
+        offset: i64,
+        length: i64,
+        advice: i64,
+    ) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Fadvise64)
+                .var(fd)
+                .value(offset)
+                .value(length)
+                .value(advice),
+        )
+    }
+
+    pub fn readahead(&mut self, fd: i64, offset: i64, count: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Readahead)
+                .var(fd)
+                .value(offset)
+                .value(count),
+        )
+    }
+
+    // closes every alias of fd as well
+    pub fn close(&mut self, fd: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Close).var(fd))?;
+        for alias in self.fd_aliases(fd) {
+            if self.active_fds.contains(&alias) {
+                self.try_remove_fd(alias)?;
+            }
+        }
+        Ok(())
+    }
+
+    // returns the index of the new fd variable
+    pub fn dup(&mut self, fd: i64) -> Result<i64, HdError> {
+        let kind = self.try_get_variable(fd)?.kind;
+        self.push_with_ret(
+            SyscallBuilder::new(SysNo::Dup).var(fd),
+            VariableType::Long(-1),
+            kind,
+        )
+    }
+
+    // returns the index of the variable that now holds newfd
+    pub fn dup2(&mut self, oldfd: i64, newfd: i64) -> Result<i64, HdError> {
+        self.dup_onto(SyscallBuilder::new(SysNo::Dup2), oldfd, newfd, None)
+    }
+
+    // flags may contain O_CLOEXEC
+    pub fn dup3(&mut self, oldfd: i64, newfd: i64, flags: i64) -> Result<i64, HdError> {
+        self.dup_onto(SyscallBuilder::new(SysNo::Dup3), oldfd, newfd, Some(flags))
+    }
+
+    fn dup_onto(
+        &mut self,
+        builder: SyscallBuilder,
+        oldfd: i64,
+        newfd: i64,
+        flags: Option<i64>,
+    ) -> Result<i64, HdError> {
+        let kind = self.try_get_variable(oldfd)?.kind;
+        self.try_get_variable(newfd)?;
+        // the result holds the same number as newfd and takes its place in
+        // the active lists, newfd keeps its kind so earlier uses stay valid
+        let name = format!("v{}", self.variables.len());
+        self.variables
+            .push(Variable::new(&name, VariableType::Long(-1), kind));
+        let ret = (self.variables.len() - 1) as i64;
+        let builder = builder.ret(ret).var(oldfd).var(newfd);
+        let builder = match flags {
+            Some(f) => builder.value(f),
+            None => builder,
+        };
+        if let Err(e) = self.push_checked(builder) {
+            self.variables.pop();
+            return Err(e);
+        }
+        // whatever newfd referred to is closed and replaced by oldfd's file
+        for alias in self.fd_aliases(newfd) {
+            if self.active_fds.contains(&alias) {
+                self.try_remove_fd(alias)?;
+            }
+        }
+        self.try_add_fd(ret)?;
+        Ok(ret)
+    }
+
+    pub fn chmod(&mut self, path: &str, mode: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Chmod)
+                .var(path_index)
+                .value(mode),
+        )?;
+        self.update_meta_follow(path, |m| m.mode = Some(mode & 0o7777));
+        Ok(())
+    }
+
+    pub fn fchmod(&mut self, fd: i64, mode: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Fchmod).var(fd).value(mode))?;
+        if let Some(path) = self.fd_path(fd) {
+            self.update_meta_follow(&path, |m| m.mode = Some(mode & 0o7777));
+        }
+        Ok(())
+    }
+
+    pub fn fchmodat(&mut self, dirfd: i64, path: &str, mode: i64) -> Result<(), HdError> {
+        let full = self.resolve_at(dirfd, path);
+        let path_index = self.path_index(path);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Fchmodat), dirfd);
+        self.push_checked(builder.var(path_index).value(mode))?;
+        if let Some(full) = full {
+            self.update_meta_follow(&full, |m| m.mode = Some(mode & 0o7777));
+        }
+        Ok(())
+    }
+
+    // uid or gid of -1 leaves it unchanged
+    pub fn chown(&mut self, path: &str, uid: i64, gid: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Chown)
+                .var(path_index)
+                .value(uid)
+                .value(gid),
+        )?;
+        self.update_meta_follow(path, |m| set_owner(m, uid, gid));
+        Ok(())
+    }
+
+    pub fn fchown(&mut self, fd: i64, uid: i64, gid: i64) -> Result<(), HdError> {
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Fchown)
+                .var(fd)
+                .value(uid)
+                .value(gid),
+        )?;
+        if let Some(path) = self.fd_path(fd) {
+            self.update_meta_follow(&path, |m| set_owner(m, uid, gid));
+        }
+        Ok(())
+    }
+
+    // does not follow a symlink at path
+    pub fn lchown(&mut self, path: &str, uid: i64, gid: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Lchown)
+                .var(path_index)
+                .value(uid)
+                .value(gid),
+        )?;
+        self.update_meta(path, |m| set_owner(m, uid, gid));
+        Ok(())
+    }
+
+    pub fn umask(&mut self, mask: i64) -> Result<(), HdError> {
+        self.push_checked(SyscallBuilder::new(SysNo::Umask).value(mask))
+    }
+
+    // times is [atime, mtime] as (tv_sec, tv_nsec), None sets both to now
+    pub fn utimensat(
+        &mut self,
+        dirfd: i64,
+        path: &str,
+        times: Option<[(i64, i64); 2]>,
+        flags: i64,
+    ) -> Result<(), HdError> {
+        let full = self.resolve_at(dirfd, path);
+        let path_index = self.path_index(path);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Utimensat), dirfd);
+        let builder = builder.var(path_index);
+        let builder = match times {
+            Some(t) => {
+                let mut bytes = Vec::<u8>::new();
+                for (sec, nsec) in t.iter() {
+                    bytes.extend_from_slice(&sec.to_le_bytes());
+                    bytes.extend_from_slice(&nsec.to_le_bytes());
+                }
+                let size = bytes.len() as u32;
+                builder.var(self.create_variable(VariableType::UCharPtr(Some(bytes), size)))
+            }
+            None => builder.value(0),
+        };
+        self.push_checked(builder.value(flags))?;
+        let [atime, mtime] = times.unwrap_or([(0, UTIME_NOW), (0, UTIME_NOW)]);
+        let update = |m: &mut FileMeta| {
+            if atime.1 != UTIME_OMIT {
+                m.atime = Some(atime);
+            }
+            if mtime.1 != UTIME_OMIT {
+                m.mtime = Some(mtime);
+            }
+        };
+        match full {
+            Some(full) if flags & AT_SYMLINK_NOFOLLOW != 0 => self.update_meta(&full, update),
+            Some(full) => self.update_meta_follow(&full, update),
+            None => {}
+        }
+        Ok(())
+    }
+
+    // mode is S_IFIFO, S_IFCHR, S_IFBLK, S_IFSOCK or S_IFREG or'ed with permissions
+    pub fn mknod(&mut self, path: &str, mode: i64, dev: i64) -> Result<(), HdError> {
+        let path_index = self.path_index(path);
+        self.push_checked(
+            SyscallBuilder::new(SysNo::Mknod)
+                .var(path_index)
+                .value(mode)
+                .value(dev),
+        )?;
+        self.learn_node(path, mode)
+    }
+
+    pub fn mknodat(&mut self, dirfd: i64, path: &str, mode: i64, dev: i64) -> Result<(), HdError> {
+        let full = self.resolve_at(dirfd, path);
+        let path_index = self.path_index(path);
+        let builder = Self::dirfd_arg(SyscallBuilder::new(SysNo::Mknodat), dirfd);
+        self.push_checked(builder.var(path_index).value(mode).value(dev))?;
+        match full {
+            Some(full) => self.learn_node(&full, mode),
+            None => Ok(()),
+        }
+    }
+
+    fn learn_node(&mut self, path: &str, mode: i64) -> Result<(), HdError> {
+        match FileType::from_mode(mode) {
+            // mknod refuses these, nothing is created
+            FileType::Dir | FileType::Symlink | FileType::Unknown => Ok(()),
+            ftype => self.learn_created(path, ftype, mode),
+        }
+    }
+}
+
+// device number as glibc's makedev encodes it
+pub fn makedev(major: i64, minor: i64) -> i64 {
+    ((major & 0xfffff000) << 32)
+        | ((major & 0xfff) << 8)
+        | ((minor & 0xffffff00) << 12)
+        | (minor & 0xff)
+}
+
+// chown semantics: -1 keeps the current owner or group
+fn set_owner(meta: &mut FileMeta, uid: i64, gid: i64) {
+    if uid != -1 {
+        meta.uid = Some(uid);
+    }
+    if gid != -1 {
+        meta.gid = Some(gid);
+    }
+}
+
+// variable index passed as arg
+pub fn arg_var(arg: Option<&Arg>) -> Option<i64> {
+    match arg {
+        Some(a) if a.is_variable => a.index.map(|i| i as i64),
+        _ => None,
+    }
+}
+
+// dirfd passed as arg, either an fd variable or AT_FDCWD
+pub fn arg_dirfd(arg: Option<&Arg>) -> Option<i64> {
+    match arg {
+        Some(a) if a.is_variable => a.index.map(|i| i as i64),
+        Some(a) => a.value.filter(|x| *x == AT_FDCWD),
+        None => None,
+    }
+}
+
+// path with the prefix from replaced by to, if it lies at or below from
+fn relocate(path: &str, from: &str, to: &str) -> Option<String> {
+    if path == from {
+        return Some(String::from(to));
+    }
+    path.strip_prefix(&format!("{}/", from))
+        .map(|rest| format!("{}/{}", to, rest))
+}
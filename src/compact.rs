@@ -0,0 +1,129 @@
+use crate::*;
+
+impl Program {
+    // drop the variables no syscall passes or assigns and renumber the rest,
+    // returns how many were dropped. the path variables of file objects stay,
+    // and the two buffers of prepare_buffers stay together so that SRC8192,
+    // DEST8192 and PATHSTART keep their meaning whenever the buffers are used
+    pub fn compact(&mut self) -> Result<usize, HdError> {
+        let mut used = vec![false; self.variables.len()];
+        let mut mark = |index: i64| -> Result<(), HdError> {
+            *used
+                .get_mut(index as usize)
+                .ok_or(HdError::DanglingIndex(index))? = true;
+            Ok(())
+        };
+        for s in self.syscalls.iter() {
+            if s.ret_index != -1 {
+                mark(s.ret_index)?;
+            }
+            for index in s.args.iter().filter_map(|x| arg_var(Some(x))) {
+                mark(index)?;
+            }
+        }
+        for fobj in self
+            .avail_files
+            .iter()
+            .chain(self.avail_dirs.iter())
+            .chain(self.avail_non_dirs.iter())
+        {
+            mark(fobj.fd_index)?;
+        }
+        if self.has_buffers()
+            && (used[Program::SRC8192 as usize] || used[Program::DEST8192 as usize])
+        {
+            used[Program::SRC8192 as usize] = true;
+            used[Program::DEST8192 as usize] = true;
+        }
+
+        // fds and mappings that no syscall touches were never opened
+        let is_used = |index: &i64| used.get(*index as usize).copied().unwrap_or(false);
+        self.active_fds.retain(is_used);
+        self.active_file_fds.retain(is_used);
+        self.active_dir_fds.retain(is_used);
+        let maps: Vec<(i64, i64)> = self
+            .active_map_base_idx
+            .iter()
+            .copied()
+            .zip(self.active_map_size.iter().copied())
+            .filter(|(index, _)| is_used(index))
+            .collect();
+        self.active_map_base_idx = maps.iter().map(|x| x.0).collect();
+        self.active_map_size = maps.iter().map(|x| x.1).collect();
+
+        let mut next = 0;
+        let map: Vec<Option<i64>> = used
+            .iter()
+            .map(|u| {
+                if !u {
+                    return None;
+                }
+                next += 1;
+                Some(next - 1)
+            })
+            .collect();
+        let dropped = used.iter().filter(|u| !**u).count();
+        self.renumber_variables(&map)?;
+        Ok(dropped)
+    }
+
+    // whether the first two variables are the buffers of prepare_buffers
+    pub fn has_buffers(&self) -> bool {
+        [Program::SRC8192, Program::DEST8192].iter().all(|i| {
+            self.variables.get(*i as usize).is_some_and(|v| {
+                matches!(*v.var_type, VariableType::UCharPtr(_, size) if size == Program::PAGE_SIZE * 2)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_unused_variables_and_renumbers_the_rest() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        prog.create_variable(VariableType::Long(5));
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.create_str("unused");
+        prog.write(fd, Program::SRC8192, 10).unwrap();
+        let c = prog.try_to_c().unwrap();
+        assert_eq!(prog.compact().unwrap(), 2);
+        assert_eq!(prog.validate(), vec![]);
+        // the open and its path moved down over the dropped Long
+        let open = &prog.syscalls[0];
+        assert_eq!(arg_var(open.args.first()), Some(2));
+        assert_eq!(open.ret_index, 3);
+        assert_eq!(arg_var(prog.syscalls[1].args.first()), Some(3));
+        assert_eq!(prog.active_fds, vec![3]);
+        assert_eq!(prog.avail_files[0].fd_index, 2);
+        assert_eq!(prog.variables.len(), 4);
+        assert!(prog
+            .variables
+            .iter()
+            .enumerate()
+            .all(|(i, v)| v.name == format!("v{}", i)));
+        // the unused declarations are gone from the C source
+        assert!(prog.try_to_c().unwrap().len() < c.len());
+        assert_eq!(prog.compact().unwrap(), 0);
+    }
+
+    #[test]
+    fn buffers_stay_together() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.read(fd, Program::DEST8192, 10).unwrap();
+        assert_eq!(prog.compact().unwrap(), 0);
+        assert!(prog.has_buffers());
+
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        prog.mkdir("d", 0o755).unwrap();
+        assert_eq!(prog.compact().unwrap(), 2);
+        assert!(!prog.has_buffers());
+        assert_eq!(prog.validate(), vec![]);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::*;
+
+impl Program {
+    // position and id of every crash point, in program order
+    pub fn crash_points(&self) -> Vec<(usize, i64)> {
+        self.syscalls
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.nr == SysNo::CrashPoint)
+            .filter_map(|(pos, s)| Some((pos, s.args.first()?.value?)))
+            .collect()
+    }
+
+    // put a crash point with id before the syscall at pos, or at the end
+    pub fn try_insert_crash_point(&mut self, pos: usize, id: i64) -> Result<(), HdError> {
+        if pos > self.syscalls.len() {
+            return Err(HdError::NoSyscall(pos));
+        }
+        let s = SyscallBuilder::new(SysNo::CrashPoint)
+            .value(id)
+            .build(self)?;
+        self.syscalls.insert(pos, s);
+        Ok(())
+    }
+
+    // a crash point after every syscall, numbered from 0
+    pub fn insert_crash_points(&mut self) {
+        let mut id = 0;
+        let mut pos = 0;
+        while pos < self.syscalls.len() {
+            let nr = self.syscalls[pos].nr;
+            pos += 1;
+            if nr.is_pseudo() {
+                continue;
+            }
+            let mut s = Syscall::new(SysNo::CrashPoint);
+            s.add_arg(id, false);
+            self.syscalls.insert(pos, s);
+            id += 1;
+            pos += 1;
+        }
+    }
+
+    pub fn remove_crash_points(&mut self) {
+        self.syscalls.retain(|s| s.nr != SysNo::CrashPoint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, ExitStatus};
+
+    fn program() -> Program {
+        let mut prog = Program::new();
+        prog.mkdir("d", 0o755).unwrap();
+        prog.mkdir("e", 0o755).unwrap();
+        prog.insert_crash_points();
+        prog
+    }
+
+    // a fresh directory for the test, removed again when dropped
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "hdrepresentation-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // build the C source of prog into dir/p
+    fn compile(prog: &Program, options: &COptions, dir: &Path) {
+        let src = dir.join("p.c");
+        std::fs::write(&src, prog.try_to_c_with(options).unwrap()).unwrap();
+        let status = Command::new("cc")
+            .arg("-w")
+            .arg("-o")
+            .arg(dir.join("p"))
+            .arg(&src)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    // run dir/p in a new directory dir/name, with the crash point id if given
+    fn run(dir: &Path, name: &str, id: Option<i64>) -> (ExitStatus, PathBuf) {
+        let cwd = dir.join(name);
+        std::fs::create_dir(&cwd).unwrap();
+        let mut cmd = Command::new(dir.join("p"));
+        cmd.current_dir(&cwd).stderr(std::process::Stdio::null());
+        if let Some(id) = id {
+            cmd.arg(id.to_string());
+        }
+        (cmd.status().unwrap(), cwd)
+    }
+
+    #[test]
+    fn crash_points_follow_each_syscall() {
+        let mut prog = program();
+        assert_eq!(prog.crash_points(), vec![(1, 0), (3, 1)]);
+        prog.remove_crash_points();
+        prog.try_insert_crash_point(0, 7).unwrap();
+        assert_eq!(prog.crash_points(), vec![(0, 7)]);
+        assert!(prog.try_insert_crash_point(4, 8).is_err());
+    }
+
+    #[test]
+    fn only_the_given_crash_point_fires() {
+        let dir = Dir::new("crash-ctl");
+        let ctl = dir.0.join("ctl");
+        let options = COptions {
+            crash: CrashHook::ControlFile(ctl.to_str().unwrap().to_string()),
+            ..COptions::default()
+        };
+        compile(&program(), &options, &dir.0);
+        let (status, _) = run(&dir.0, "one", Some(1));
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&ctl).unwrap(), "1\n");
+        std::fs::remove_file(&ctl).unwrap();
+        let (status, _) = run(&dir.0, "all", None);
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&ctl).unwrap(), "0\n1\n");
+    }
+
+    #[test]
+    fn sync_abort_stops_at_its_crash_point_only() {
+        let dir = Dir::new("crash-abort");
+        compile(&program(), &COptions::default(), &dir.0);
+        let (status, cwd) = run(&dir.0, "first", Some(0));
+        assert_eq!(status.signal(), Some(libc::SIGABRT));
+        assert!(cwd.join("d").exists() && !cwd.join("e").exists());
+        let (status, cwd) = run(&dir.0, "none", None);
+        assert!(status.success());
+        assert!(cwd.join("e").exists());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::*;
+
+impl Program {
+    // the first cut syscalls of self followed by the syscalls of other from
+    // start on. other's variables are appended and renumbered, paths are
+    // shared, and uses of what other's prefix assigned are pointed at an
+    // earlier variable that fits or removed
+    pub fn crossover(&self, cut: usize, other: &Program, start: usize) -> Result<Program, HdError> {
+        if cut > self.syscalls.len() {
+            return Err(HdError::NoSyscall(cut));
+        }
+        if start > other.syscalls.len() {
+            return Err(HdError::NoSyscall(start));
+        }
+        let mut prog = self.clone();
+        prog.syscalls.truncate(cut);
+
+        let remap = prog.append_variables(other);
+        let index_of = |index: i64| -> Result<i64, HdError> {
+            remap
+                .get(index as usize)
+                .copied()
+                .ok_or(HdError::DanglingIndex(index))
+        };
+        for s in other.syscalls[start..].iter() {
+            let mut s = s.clone();
+            if s.ret_index != -1 {
+                s.ret_index = index_of(s.ret_index)?;
+            }
+            for arg in s.args.iter_mut() {
+                if let Some(index) = arg_var(Some(arg)) {
+                    *arg = Arg::new(index_of(index)?, true);
+                }
+            }
+            prog.syscalls.push(s);
+        }
+
+        // variables other's syscalls assign, only the suffix assigns them now
+        let assigned = other
+            .syscalls
+            .iter()
+            .filter(|x| x.ret_index != -1)
+            .map(|x| index_of(x.ret_index))
+            .collect::<Result<Vec<i64>, HdError>>()?;
+        let mut j = cut;
+        'syscalls: while j < prog.syscalls.len() {
+            let mut dangling: Vec<i64> = prog.syscalls[j]
+                .args
+                .iter()
+                .filter_map(|x| arg_var(Some(x)))
+                .filter(|x| assigned.contains(x))
+                .filter(|x| !prog.syscalls[..j].iter().any(|s| s.ret_index == *x))
+                .collect();
+            dangling.sort_unstable();
+            dangling.dedup();
+            for index in dangling {
+                let uses: Vec<usize> = prog.syscalls[j]
+                    .args
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, x)| arg_var(Some(x)) == Some(index))
+                    .map(|(k, _)| k)
+                    .collect();
+                match prog.substitute(index, j, &uses) {
+                    Some(substitute) => {
+                        for k in uses {
+                            prog.syscalls[j].args[k] = Arg::new(substitute, true);
+                        }
+                    }
+                    None => {
+                        prog.delete_syscall(j)?;
+                        continue 'syscalls;
+                    }
+                }
+            }
+            j += 1;
+        }
+
+        // the model rebuilds the file objects below, these stay if it
+        // loses track. paths self knows keep self's file objects
+        let known: Vec<String> = prog
+            .avail_files
+            .iter()
+            .map(|x| x.rel_path.clone())
+            .collect();
+        let lists = [
+            (&mut prog.avail_files, &other.avail_files),
+            (&mut prog.avail_dirs, &other.avail_dirs),
+            (&mut prog.avail_non_dirs, &other.avail_non_dirs),
+        ];
+        for (list, from) in lists {
+            for fobj in from.iter() {
+                if known.contains(&fobj.rel_path) {
+                    continue;
+                }
+                let mut fobj = fobj.clone();
+                fobj.fd_index = index_of(fobj.fd_index)?;
+                list.push(fobj);
+            }
+        }
+        // what the prefix opened and the suffix closed, or the suffix
+        // created, is only known from the spliced syscalls
+        prog.sync_state()?;
+        Ok(prog)
+    }
+
+    // append the variables of other under fresh names and return the new
+    // index of each, Str variables reuse an equal one already present
+    fn append_variables(&mut self, other: &Program) -> Vec<i64> {
+        let mut remap = Vec::<i64>::with_capacity(other.variables.len());
+        for v in other.variables.iter() {
+            let existing = match &*v.var_type {
+                VariableType::Str(_) => {
+                    self.variables.iter().position(|x| x.var_type == v.var_type)
+                }
+                _ => None,
+            };
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    let index = self.variables.len();
+                    let mut v = v.clone();
+                    v.name = format!("v{}", index);
+                    self.variables.push(v);
+                    index
+                }
+            };
+            remap.push(index as i64);
+        }
+        remap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(prog: &Program) -> Vec<&str> {
+        let mut paths: Vec<&str> = prog
+            .avail_files
+            .iter()
+            .map(|x| x.rel_path.as_str())
+            .collect();
+        paths.sort_unstable();
+        paths
+    }
+
+    #[test]
+    fn state_is_the_one_at_the_cut() {
+        let mut prog = Program::new();
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.close(fd).unwrap();
+        prog.open("g", O_CREAT | O_RDWR, 0o644).unwrap();
+        let child = prog.crossover(1, &Program::new(), 0).unwrap();
+        assert_eq!(child.syscalls.len(), 1);
+        assert_eq!(child.active_fds, vec![fd]);
+        assert_eq!(paths(&child), vec!["f"]);
+        assert_eq!(child.validate(), vec![]);
+    }
+
+    #[test]
+    fn suffix_variables_are_renumbered() {
+        let mut a = Program::new();
+        a.prepare_buffers();
+        a.mkdir("d", 0o755).unwrap();
+        let mut b = Program::new();
+        b.prepare_buffers();
+        let fd = b.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        b.write(fd, Program::SRC8192, 10).unwrap();
+        b.close(fd).unwrap();
+        let child = a.crossover(1, &b, 0).unwrap();
+        assert_eq!(child.syscalls.len(), 4);
+        assert_eq!(child.validate(), vec![]);
+        assert_eq!(paths(&child), vec!["d", "f"]);
+        let open = &child.syscalls[1];
+        assert_eq!(open.nr, SysNo::Open);
+        assert!(open.ret_index as usize >= a.variables.len());
+        // the write and close use the fd the spliced open assigns
+        for s in child.syscalls[2..].iter() {
+            assert_eq!(arg_var(s.args.first()), Some(open.ret_index));
+        }
+        assert!(child.active_fds.is_empty());
+    }
+
+    #[test]
+    fn uses_of_what_the_skipped_prefix_assigned_are_removed() {
+        let mut b = Program::new();
+        b.prepare_buffers();
+        let fd = b.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        b.write(fd, Program::SRC8192, 10).unwrap();
+        b.mkdir("d", 0o755).unwrap();
+        // no earlier fd to point the write at
+        let child = Program::new().crossover(0, &b, 1).unwrap();
+        assert_eq!(
+            child.syscalls.iter().map(|x| x.nr).collect::<Vec<_>>(),
+            vec![SysNo::Mkdir]
+        );
+        assert_eq!(child.validate(), vec![]);
+        assert_eq!(paths(&child), vec!["d"]);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::*;
+
+// why a syscall depends on an earlier one
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dependency {
+    // the later syscall passes the variable the earlier one assigned
+    Var(i64),
+    // the later syscall names a path the earlier one created, or a path below it
+    Path(String),
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dependency::Var(index) => write!(f, "v{}", index),
+            Dependency::Path(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+// edge from the syscall at position from to the later one at position to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DepEdge {
+    pub from: usize,
+    pub to: usize,
+    pub dep: Dependency,
+}
+
+// def-use graph of a program, one node per syscall
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DepGraph {
+    pub nodes: Vec<SysNo>,
+    pub edges: Vec<DepEdge>,
+}
+
+impl DepGraph {
+    // syscalls the syscall at pos directly depends on
+    pub fn dependencies(&self, pos: usize) -> Vec<usize> {
+        let mut deps: Vec<usize> = self
+            .edges
+            .iter()
+            .filter(|x| x.to == pos)
+            .map(|x| x.from)
+            .collect();
+        deps.sort_unstable();
+        deps.dedup();
+        deps
+    }
+
+    // syscalls that directly depend on the syscall at pos
+    pub fn dependents(&self, pos: usize) -> Vec<usize> {
+        let mut deps: Vec<usize> = self
+            .edges
+            .iter()
+            .filter(|x| x.from == pos)
+            .map(|x| x.to)
+            .collect();
+        deps.sort_unstable();
+        deps.dedup();
+        deps
+    }
+
+    // every syscall that depends on the syscall at pos, directly or not
+    pub fn depends_on(&self, pos: usize) -> Vec<usize> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut todo = vec![pos];
+        while let Some(x) = todo.pop() {
+            for y in self.dependents(x) {
+                if !seen[y] {
+                    seen[y] = true;
+                    todo.push(y);
+                }
+            }
+        }
+        (0..seen.len()).filter(|x| seen[*x]).collect()
+    }
+
+    // graphviz source, variable edges solid and path edges dashed
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph program {\n");
+        for (pos, nr) in self.nodes.iter().enumerate() {
+            out.push_str(&format!("    s{} [label=\"{}: {}\"];\n", pos, pos, nr));
+        }
+        for edge in self.edges.iter() {
+            let style = match edge.dep {
+                Dependency::Var(_) => "",
+                Dependency::Path(_) => ", style=dashed",
+            };
+            out.push_str(&format!(
+                "    s{} -> s{} [label=\"{}\"{}];\n",
+                edge.from,
+                edge.to,
+                dot_escape(&edge.dep.to_string()),
+                style
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Program {
+    // edges from each ret_index to the later uses of the variable and from
+    // each syscall creating a path to the later syscalls naming it or a path
+    // below it
+    pub fn dep_graph(&self) -> DepGraph {
+        let mut graph = DepGraph {
+            nodes: self.syscalls.iter().map(|x| x.nr).collect(),
+            edges: Vec::<DepEdge>::new(),
+        };
+        // latest creator of each path so far
+        let mut creators = HashMap::<String, usize>::new();
+        for (pos, s) in self.syscalls.iter().enumerate() {
+            let mut edges = Vec::<DepEdge>::new();
+            for index in s.args.iter().filter_map(|x| arg_var(Some(x))) {
+                let def = self.syscalls[..pos]
+                    .iter()
+                    .rposition(|x| x.ret_index == index);
+                if let Some(from) = def {
+                    edges.push(DepEdge {
+                        from,
+                        to: pos,
+                        dep: Dependency::Var(index),
+                    });
+                }
+            }
+            let named = self.named_paths(pos);
+            for (path, _) in named.iter() {
+                for prefix in path_prefixes(path) {
+                    if let Some(from) = creators.get(prefix) {
+                        edges.push(DepEdge {
+                            from: *from,
+                            to: pos,
+                            dep: Dependency::Path(String::from(prefix)),
+                        });
+                    }
+                }
+            }
+            for edge in edges {
+                if !graph.edges.contains(&edge) {
+                    graph.edges.push(edge);
+                }
+            }
+            for (path, created) in named {
+                if created {
+                    creators.insert(path, pos);
+                }
+            }
+        }
+        graph
+    }
+
+    // paths the syscall at pos looks up, relative to the working directory,
+    // and whether it creates them. symlink targets are not looked up
+    pub fn named_paths(&self, pos: usize) -> Vec<(String, bool)> {
+        let s = match self.syscalls.get(pos) {
+            Some(s) => s,
+            None => return Vec::new(),
+        };
+        let sig = signature(s.nr);
+        let flags = |k: usize| s.args.get(k).and_then(|x| x.value).unwrap_or(0);
+        let mut paths = Vec::<(String, bool)>::new();
+        for (k, kind) in sig.iter().enumerate() {
+            if *kind != ArgKind::Path || matches!((s.nr, k), (SysNo::Symlink | SysNo::Symlinkat, 0))
+            {
+                continue;
+            }
+            let path = match self.arg_str(s.args.get(k)) {
+                Some(p) => p,
+                None => continue,
+            };
+            let path = match k.checked_sub(1).map(|x| (sig[x], x)) {
+                // a path below a directory whose path is not known
+                Some((ArgKind::DirFd, x)) => match arg_dirfd(s.args.get(x))
+                    .and_then(|dirfd| self.resolve_at_before(dirfd, &path, pos))
+                {
+                    Some(full) => full,
+                    None => continue,
+                },
+                _ => path,
+            };
+            let created = match (s.nr, k) {
+                (SysNo::Open, 0) => flags(1) & O_CREAT != 0,
+                (SysNo::Openat, 1) => flags(2) & O_CREAT != 0,
+                (SysNo::Mkdir | SysNo::Mknod, 0) => true,
+                (SysNo::Mkdirat | SysNo::Mknodat, 1) => true,
+                (SysNo::Symlink | SysNo::Link | SysNo::Rename, 1) => true,
+                (SysNo::Symlinkat, 2) => true,
+                (SysNo::Linkat | SysNo::Renameat2, 3) => true,
+                // the exchange puts a file at the old path as well
+                (SysNo::Renameat2, 1) => flags(4) & RENAME_EXCHANGE != 0,
+                _ => false,
+            };
+            paths.push((path, created));
+        }
+        paths
+    }
+}
+
+// path and each of its parent directories, outermost first
+fn path_prefixes(path: &str) -> Vec<&str> {
+    let mut prefixes: Vec<&str> = path
+        .match_indices('/')
+        .map(|(i, _)| &path[..i])
+        .filter(|x| !x.is_empty())
+        .collect();
+    prefixes.push(path);
+    prefixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_and_path_edges() {
+        let mut prog = Program::new();
+        prog.mkdir("d", 0o755).unwrap();
+        let fd = prog.open("d/f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.close(fd).unwrap();
+        let graph = prog.dep_graph();
+        assert_eq!(
+            graph.edges,
+            vec![
+                DepEdge {
+                    from: 0,
+                    to: 1,
+                    dep: Dependency::Path(String::from("d")),
+                },
+                DepEdge {
+                    from: 1,
+                    to: 2,
+                    dep: Dependency::Var(fd),
+                },
+            ]
+        );
+        assert_eq!(graph.dependencies(2), vec![1]);
+        assert_eq!(graph.dependents(0), vec![1]);
+        assert_eq!(graph.depends_on(0), vec![1, 2]);
+    }
+
+    #[test]
+    fn dot_of_two_calls() {
+        let mut prog = Program::new();
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.close(fd).unwrap();
+        assert_eq!(
+            prog.dep_graph().to_dot(),
+            format!(
+                "digraph program {{\n    s0 [label=\"0: SYS_open\"];\n    \
+                 s1 [label=\"1: SYS_close\"];\n    s0 -> s1 [label=\"v{}\"];\n}}\n",
+                fd
+            )
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::signature::ArgKind;
+use crate::syscall::SysNo;
+use crate::types::*;
+use std::error::Error;
+use std::io;
+
+// crate-wide error so that a single malformed program does not abort the caller
+#[derive(Debug)]
+pub enum HdError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    // an index that does not point into the variables vector
+    DanglingIndex(i64),
+    // the variable at index exists but is of the wrong kind
+    TypeMismatch {
+        index: i64,
+        expected: &'static str,
+        found: FileType,
+    },
+    // an argument that has neither a value nor a variable index
+    MalformedArg(usize),
+    // the syscall got a different number of arguments than its signature
+    ArityMismatch {
+        nr: SysNo,
+        expected: usize,
+        found: usize,
+    },
+    // the argument at position arg does not fit its signature kind
+    ArgKindMismatch {
+        nr: SysNo,
+        arg: usize,
+        expected: ArgKind,
+    },
+    // an access that does not fit inside the mapping at index
+    MapOutOfBounds {
+        index: i64,
+        offset: i64,
+        length: i64,
+        size: i64,
+    },
+    // the variable cannot be turned into C source
+    Unprintable(String),
+    // the list that was asked to shrink is already empty
+    Empty(&'static str),
+    // there is no syscall at this position
+    NoSyscall(usize),
+    // the variable at index is still referenced and cannot be removed
+    InUse(i64),
+    // the syscall at position syscall reads variable index before any
+    // syscall assigns it
+    UseBeforeDef {
+        syscall: usize,
+        index: i64,
+    },
+    // the log entry for the syscall at this position names another syscall
+    LogMismatch(usize),
+    // a file the C program writes besides its syscalls is not given by an
+    // absolute path, it would end up in the directory under test
+    RelativePath(String),
+}
+
+impl fmt::Display for HdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HdError::Io(e) => write!(f, "io error: {}", e),
+            HdError::Json(e) => write!(f, "json error: {}", e),
+            HdError::DanglingIndex(index) => write!(f, "no variable at index {}", index),
+            HdError::TypeMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "variable at index {} is {:?}, expected {}",
+                index, found, expected
+            ),
+            HdError::MalformedArg(pos) => write!(f, "argument {} has no value or index", pos),
+            HdError::ArityMismatch {
+                nr,
+                expected,
+                found,
+            } => write!(f, "{} takes {} arguments, got {}", nr, expected, found),
+            HdError::ArgKindMismatch { nr, arg, expected } => {
+                write!(f, "{} argument {} must be a {}", nr, arg, expected)
+            }
+            HdError::MapOutOfBounds {
+                index,
+                offset,
+                length,
+                size,
+            } => write!(
+                f,
+                "{} bytes at offset {} do not fit mapping {} of {} bytes",
+                length, offset, index, size
+            ),
+            HdError::Unprintable(name) => write!(f, "variable {} cannot be printed as C", name),
+            HdError::Empty(what) => write!(f, "{} is empty", what),
+            HdError::NoSyscall(pos) => write!(f, "no syscall at position {}", pos),
+            HdError::InUse(index) => write!(f, "variable at index {} is still referenced", index),
+            HdError::UseBeforeDef { syscall, index } => write!(
+                f,
+                "syscall {} uses variable {} before it is assigned",
+                syscall, index
+            ),
+            HdError::LogMismatch(pos) => {
+                write!(f, "log entry {} does not match the syscall there", pos)
+            }
+            HdError::RelativePath(path) => write!(f, "{} is not an absolute path", path),
+        }
+    }
+}
+
+impl Error for HdError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HdError::Io(e) => Some(e),
+            HdError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HdError {
+    fn from(e: io::Error) -> Self {
+        HdError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for HdError {
+    fn from(e: serde_json::Error) -> Self {
+        HdError::Json(e)
+    }
+}
@@ -0,0 +1,660 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::mem::{size_of, MaybeUninit};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+// what a syscall returned, errno is 0 unless ret is -1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct SyscallResult {
+    pub ret: i64,
+    pub errno: i64,
+}
+
+impl SyscallResult {
+    // whether the parts of expected that are known agree
+    pub fn matches(&self, expected: &Expected) -> bool {
+        expected.ret.is_none_or(|x| x == self.ret) && expected.errno.is_none_or(|x| x == self.errno)
+    }
+}
+
+// runs a program inside this process, with root standing in for the
+// directory the C program would run in. root should be a fresh directory
+// the program may trash; paths that could leave it, fds the program did not
+// open and memory it did not map are refused.
+//
+// the program sees the fd numbers a C run would get, whatever this process
+// has open. unlike a C run, fifos are opened non-blocking, the
+// /proc/self/fd prefix given to paths of syscalls without a dirfd shortens
+// the longest path that works, and a memcpy into a mapping that would fault
+// fails with EFAULT. umask is shared by the whole process, so two programs
+// must not run at once
+#[derive(Debug)]
+pub struct Executor {
+    root: File,
+    crash_at: Option<i64>,
+}
+
+impl Executor {
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self, HdError> {
+        let root = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_CLOEXEC)
+            .open(root)?;
+        Ok(Self {
+            root,
+            crash_at: None,
+        })
+    }
+
+    // stop right after the crash point with this id, None runs to the end
+    pub fn set_crash_at(&mut self, id: Option<i64>) -> &mut Self {
+        self.crash_at = id;
+        self
+    }
+
+    // the result of each syscall, None for a syscall that was refused or
+    // not reached. a refused syscall leaves its return variable as it was
+    pub fn run(&self, prog: &Program) -> Result<Vec<Option<SyscallResult>>, HdError> {
+        for s in prog.syscalls.iter() {
+            if s.ret_index != -1 {
+                prog.try_get_variable(s.ret_index)?;
+            }
+            s.check(prog)?;
+        }
+        let mut run = Run::new(prog, self.root.as_raw_fd() as i64)?;
+        let mut results = vec![None; prog.syscalls.len()];
+        for (pos, s) in prog.syscalls.iter().enumerate() {
+            if s.nr == SysNo::CrashPoint {
+                results[pos] = Some(SyscallResult::default());
+                if self.crash_at.is_some() && self.crash_at == s.args.first().map(|a| run.value(a))
+                {
+                    break;
+                }
+                continue;
+            }
+            results[pos] = run.step(prog, s);
+        }
+        Ok(results)
+    }
+}
+
+// MAP_FAILED, the value of a mapping that was never made
+const NO_MAP: i64 = -1;
+
+// MADV_HWPOISON and MADV_SOFT_OFFLINE act on the physical pages, which
+// other mappings may share
+const UNSAFE_ADVICE: &[i64] = &[libc::MADV_HWPOISON as i64, libc::MADV_SOFT_OFFLINE as i64];
+
+#[derive(Debug, Clone, Copy)]
+struct Mapping {
+    len: i64,
+    prot: i64,
+}
+
+// state of one run, whatever the program left open or mapped is released
+// when it is dropped
+struct Run {
+    root: i64,
+    // value of each variable, the address of its memory for a buffer
+    values: Vec<i64>,
+    // size of each buffer variable
+    sizes: Vec<Option<i64>>,
+    // memory of the buffer variables, values point into it
+    _buffers: Vec<Vec<u8>>,
+    // fds opened by the program, from the number a C run would have got to
+    // the one this process got
+    fds: BTreeMap<i64, i64>,
+    // mappings made by the program by address
+    maps: BTreeMap<i64, Mapping>,
+    umask: libc::mode_t,
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        unsafe {
+            for fd in self.fds.values() {
+                libc::close(*fd as i32);
+            }
+            for (addr, map) in self.maps.iter() {
+                libc::munmap(*addr as *mut libc::c_void, map.len as usize);
+            }
+            libc::umask(self.umask);
+        }
+    }
+}
+
+impl Run {
+    fn new(prog: &Program, root: i64) -> Result<Self, HdError> {
+        let mut values = vec![0; prog.variables.len()];
+        let mut sizes = vec![None; prog.variables.len()];
+        let mut buffers = Vec::<Vec<u8>>::new();
+        for (i, var) in prog.variables.iter().enumerate() {
+            let mut buffer = match &*var.var_type {
+                VariableType::Long(value) => {
+                    values[i] = *value;
+                    continue;
+                }
+                VariableType::VoidPtr => continue,
+                VariableType::MmapBase => {
+                    values[i] = NO_MAP;
+                    continue;
+                }
+                // same checks as the C declaration
+                VariableType::Str(s) => CString::new(&**s)
+                    .map_err(|_| HdError::Unprintable(var.name.clone()))?
+                    .into_bytes_with_nul(),
+                VariableType::UCharPtr(value, size) => {
+                    let mut buffer = vec![0; *size as usize];
+                    if let Some(v) = value {
+                        if v.len() > buffer.len() {
+                            return Err(HdError::Unprintable(var.name.clone()));
+                        }
+                        buffer[..v.len()].copy_from_slice(v);
+                    }
+                    buffer
+                }
+                VariableType::Unknown => return Err(HdError::Unprintable(var.name.clone())),
+            };
+            // moving the vec into buffers does not move its memory
+            values[i] = buffer.as_mut_ptr() as i64;
+            sizes[i] = Some(buffer.len() as i64);
+            buffers.push(buffer);
+        }
+        let umask = unsafe {
+            let umask = libc::umask(0o022);
+            libc::umask(umask);
+            umask
+        };
+        Ok(Self {
+            root,
+            values,
+            sizes,
+            _buffers: buffers,
+            fds: BTreeMap::new(),
+            maps: BTreeMap::new(),
+            umask,
+        })
+    }
+
+    fn value(&self, arg: &Arg) -> i64 {
+        match arg_var(Some(arg)) {
+            Some(index) => self.values[index as usize],
+            None => arg.value.unwrap_or(0),
+        }
+    }
+
+    // the raw arguments of s, None if it is not safe to run
+    fn args(&self, prog: &Program, s: &Syscall, paths: &mut Vec<CString>) -> Option<[i64; 6]> {
+        let sig = signature(s.nr);
+        let mut args = [0; 6];
+        for (k, (kind, arg)) in sig.iter().zip(s.args.iter()).enumerate() {
+            let value = self.value(arg);
+            args[k] = match kind {
+                // newfd of dup2 would replace whatever this process has
+                // open there
+                ArgKind::Fd if k == 1 && matches!(s.nr, SysNo::Dup2 | SysNo::Dup3) => {
+                    match self.fds.get(&value) {
+                        Some(fd) => *fd,
+                        None if value < 0 => value,
+                        None => return None,
+                    }
+                }
+                // an fd the program does not hold fails like a closed one
+                ArgKind::Fd => self.fds.get(&value).copied().unwrap_or(-1),
+                ArgKind::DirFd if value == AT_FDCWD => self.root,
+                ArgKind::DirFd => self.fds.get(&value).copied().unwrap_or(-1),
+                ArgKind::Path => {
+                    let path = prog.arg_str(Some(arg))?;
+                    if path.starts_with('/') || path.split('/').any(|x| x == "..") {
+                        return None;
+                    }
+                    let symlink_target =
+                        k == 0 && matches!(s.nr, SysNo::Symlink | SysNo::Symlinkat);
+                    let at = k > 0 && sig[k - 1] == ArgKind::DirFd;
+                    if symlink_target || at || path.is_empty() {
+                        value
+                    } else {
+                        let path = format!("/proc/self/fd/{}/{}", self.root, path);
+                        let path = CString::new(path).ok()?;
+                        let ptr = path.as_ptr() as i64;
+                        paths.push(path);
+                        ptr
+                    }
+                }
+                _ => value,
+            };
+        }
+        Some(args)
+    }
+
+    // bytes the kernel may touch through a buffer argument, i64::MAX for a
+    // pointer any access through faults, None for memory the program does
+    // not own
+    fn capacity(&self, arg: &Arg, value: i64) -> Option<i64> {
+        if let Some(size) = arg_var(Some(arg)).and_then(|i| self.sizes[i as usize]) {
+            return Some(size);
+        }
+        match self.maps.get(&value) {
+            Some(map) => Some(map.len),
+            None if value == 0 || value == NO_MAP => Some(i64::MAX),
+            None => None,
+        }
+    }
+
+    // whether the buffers of s fit what the kernel reads or writes
+    fn check_buffers(&self, s: &Syscall, args: &[i64; 6]) -> bool {
+        let sig = signature(s.nr);
+        match s.nr {
+            SysNo::Mmap => return args[3] & MAP_FIXED == 0,
+            SysNo::Munmap | SysNo::Msync | SysNo::Madvise | SysNo::Mremap => {
+                if s.nr == SysNo::Madvise && UNSAFE_ADVICE.contains(&args[2]) {
+                    return false;
+                }
+                if s.nr == SysNo::Mremap && args[3] & MREMAP_FIXED != 0 {
+                    return false;
+                }
+                // not page aligned, EINVAL
+                if args[0] == NO_MAP {
+                    return true;
+                }
+                return self
+                    .maps
+                    .get(&args[0])
+                    .is_some_and(|map| (0..=map.len).contains(&args[1]));
+            }
+            _ => {}
+        }
+        for (k, kind) in sig.iter().enumerate() {
+            if *kind != ArgKind::Buffer {
+                continue;
+            }
+            let capacity = match self.capacity(&s.args[k], args[k]) {
+                Some(x) => x,
+                None => return false,
+            };
+            let length = match (sig.get(k + 1), s.nr) {
+                (Some(ArgKind::Length), _) => args[k + 1],
+                (_, SysNo::Fstat | SysNo::Stat | SysNo::Lstat | SysNo::Fstatat) => {
+                    size_of::<libc::stat>() as i64
+                }
+                (_, SysNo::Utimensat) => size_of::<[libc::timespec; 2]>() as i64,
+                _ => 0,
+            };
+            if capacity != i64::MAX && !(0..=capacity).contains(&length) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // make an fd this process cannot block on or reach a device through,
+    // false if it must not be opened
+    fn check_open(&self, s: &Syscall, args: &mut [i64; 6]) -> bool {
+        let (dirfd, path, flags) = match s.nr {
+            SysNo::Open => (libc::AT_FDCWD as i64, args[0], 1),
+            SysNo::Openat => (args[0], args[1], 2),
+            _ => return true,
+        };
+        let mut st = MaybeUninit::<libc::stat>::uninit();
+        let r = unsafe {
+            libc::fstatat(
+                dirfd as i32,
+                path as *const libc::c_char,
+                st.as_mut_ptr(),
+                0,
+            )
+        };
+        if r != 0 {
+            return true;
+        }
+        match unsafe { st.assume_init() }.st_mode & libc::S_IFMT {
+            libc::S_IFCHR | libc::S_IFBLK => false,
+            libc::S_IFIFO => {
+                args[flags] |= O_NONBLOCK;
+                true
+            }
+            _ => true,
+        }
+    }
+
+    fn step(&mut self, prog: &Program, s: &Syscall) -> Option<SyscallResult> {
+        let mut paths = Vec::<CString>::new();
+        let mut args = self.args(prog, s, &mut paths)?;
+        if s.nr == SysNo::MemcpyToMap {
+            return self.memcpy_to_map(s, &args);
+        }
+        if !self.check_buffers(s, &args) || !self.check_open(s, &mut args) {
+            return None;
+        }
+        let nr = sys_number(s.nr)?;
+        let mut result = unsafe { raw_syscall(nr, &args) };
+        if result.errno == 0 {
+            match s.nr {
+                SysNo::Open | SysNo::Openat | SysNo::Dup => {
+                    if !self.check_fd(result.ret) {
+                        return None;
+                    }
+                    let fd = self.next_fd();
+                    self.fds.insert(fd, result.ret);
+                    result.ret = fd;
+                }
+                // newfd keeps its number and now refers to the file of oldfd
+                SysNo::Dup2 | SysNo::Dup3 => result.ret = self.value(&s.args[1]),
+                // lengths the kernel accepted round without overflow
+                SysNo::Mmap => {
+                    let map = Mapping {
+                        len: page_round(args[1]).unwrap_or(i64::MAX),
+                        prot: args[2],
+                    };
+                    self.maps.insert(result.ret, map);
+                }
+                SysNo::Munmap => self.unmap(args[0], args[1]),
+                SysNo::Mremap => {
+                    let prot = self.maps.get(&args[0]).map_or(0, |x| x.prot);
+                    if args[3] & libc::MREMAP_DONTUNMAP as i64 == 0 {
+                        self.unmap(args[0], args[1]);
+                    }
+                    let map = Mapping {
+                        len: page_round(args[2]).unwrap_or(i64::MAX),
+                        prot,
+                    };
+                    self.maps.insert(result.ret, map);
+                }
+                _ => {}
+            }
+        }
+        // close releases the fd even if it fails
+        if s.nr == SysNo::Close && result.errno != libc::EBADF as i64 {
+            self.fds.remove(&self.value(&s.args[0]));
+        }
+        if s.ret_index != -1 && self.sizes[s.ret_index as usize].is_none() {
+            self.values[s.ret_index as usize] = result.ret;
+        }
+        Some(result)
+    }
+
+    // the number a C run would get for its next fd, the lowest one above
+    // stdio that is free
+    fn next_fd(&self) -> i64 {
+        let mut fd = 3;
+        while self.fds.contains_key(&fd) {
+            fd += 1;
+        }
+        fd
+    }
+
+    // keep a fifo that was opened O_RDWR from blocking, false if the open
+    // raced with a device taking the place of the file and it was closed
+    fn check_fd(&self, fd: i64) -> bool {
+        let mut st = MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(fd as i32, st.as_mut_ptr()) } != 0 {
+            return true;
+        }
+        match unsafe { st.assume_init() }.st_mode & libc::S_IFMT {
+            libc::S_IFCHR | libc::S_IFBLK => {
+                unsafe { libc::close(fd as i32) };
+                false
+            }
+            libc::S_IFIFO => {
+                unsafe {
+                    let flags = libc::fcntl(fd as i32, libc::F_GETFL);
+                    libc::fcntl(fd as i32, libc::F_SETFL, flags | libc::O_NONBLOCK);
+                }
+                true
+            }
+            _ => true,
+        }
+    }
+
+    // forget the pages munmap removed from the mapping at addr
+    fn unmap(&mut self, addr: i64, len: i64) {
+        if let Some(map) = self.maps.remove(&addr) {
+            let cut = page_round(len).unwrap_or(i64::MAX);
+            if cut < map.len {
+                let rest = Mapping {
+                    len: map.len - cut,
+                    prot: map.prot,
+                };
+                self.maps.insert(addr + cut, rest);
+            }
+        }
+    }
+
+    // copy with process_vm_writev so that a fault is an error instead of a
+    // signal
+    fn memcpy_to_map(&self, s: &Syscall, args: &[i64; 6]) -> Option<SyscallResult> {
+        // skipped like the C program does
+        if args[0] == NO_MAP {
+            return Some(SyscallResult::default());
+        }
+        let map = self.maps.get(&args[0])?;
+        let (offset, src, len) = (args[1], args[2], args[3]);
+        if offset < 0 || len < 0 || offset.checked_add(len).is_none_or(|end| end > map.len) {
+            return None;
+        }
+        let capacity = self.capacity(&s.args[2], src)?;
+        if capacity == i64::MAX || len > capacity {
+            return None;
+        }
+        let fault = SyscallResult {
+            ret: -1,
+            errno: libc::EFAULT as i64,
+        };
+        if map.prot & PROT_WRITE == 0 {
+            return Some(fault);
+        }
+        let local = libc::iovec {
+            iov_base: src as *mut libc::c_void,
+            iov_len: len as usize,
+        };
+        let remote = libc::iovec {
+            iov_base: (args[0] + offset) as *mut libc::c_void,
+            iov_len: len as usize,
+        };
+        let written = unsafe { libc::process_vm_writev(libc::getpid(), &local, 1, &remote, 1, 0) };
+        match written as i64 == len {
+            true => Some(SyscallResult::default()),
+            false => Some(fault),
+        }
+    }
+}
+
+unsafe fn raw_syscall(nr: libc::c_long, args: &[i64; 6]) -> SyscallResult {
+    let ret = libc::syscall(
+        nr,
+        args[0] as libc::c_long,
+        args[1] as libc::c_long,
+        args[2] as libc::c_long,
+        args[3] as libc::c_long,
+        args[4] as libc::c_long,
+        args[5] as libc::c_long,
+    ) as i64;
+    let errno = match ret {
+        -1 => io::Error::last_os_error().raw_os_error().unwrap_or(0) as i64,
+        _ => 0,
+    };
+    SyscallResult { ret, errno }
+}
+
+// the number num_to_name names, None for a pseudo-operation
+fn sys_number(nr: SysNo) -> Option<libc::c_long> {
+    let x = match nr {
+        SysNo::Open => libc::SYS_open,
+        SysNo::Read => libc::SYS_read,
+        SysNo::Write => libc::SYS_write,
+        SysNo::Lseek => libc::SYS_lseek,
+        SysNo::Getdents => libc::SYS_getdents64,
+        SysNo::Pread => libc::SYS_pread64,
+        SysNo::Pwrite => libc::SYS_pwrite64,
+        SysNo::Fstat => libc::SYS_fstat,
+        SysNo::Stat => libc::SYS_stat,
+        SysNo::Lstat => libc::SYS_lstat,
+        SysNo::Rename => libc::SYS_rename,
+        SysNo::Fsync => libc::SYS_fsync,
+        SysNo::Fdatasync => libc::SYS_fdatasync,
+        SysNo::Syncfs => libc::SYS_syncfs,
+        SysNo::Sendfile => libc::SYS_sendfile,
+        SysNo::Access => libc::SYS_access,
+        SysNo::Ftruncate => libc::SYS_ftruncate,
+        SysNo::Truncate => libc::SYS_truncate,
+        SysNo::Mkdir => libc::SYS_mkdir,
+        SysNo::Rmdir => libc::SYS_rmdir,
+        SysNo::Link => libc::SYS_link,
+        SysNo::Unlink => libc::SYS_unlink,
+        SysNo::Symlink => libc::SYS_symlink,
+        SysNo::Setxattr => libc::SYS_setxattr,
+        SysNo::Getxattr => libc::SYS_getxattr,
+        SysNo::Removexattr => libc::SYS_removexattr,
+        SysNo::Listxattr => libc::SYS_listxattr,
+        SysNo::Lsetxattr => libc::SYS_lsetxattr,
+        SysNo::Fsetxattr => libc::SYS_fsetxattr,
+        SysNo::Lgetxattr => libc::SYS_lgetxattr,
+        SysNo::Fgetxattr => libc::SYS_fgetxattr,
+        SysNo::Llistxattr => libc::SYS_llistxattr,
+        SysNo::Flistxattr => libc::SYS_flistxattr,
+        SysNo::Lremovexattr => libc::SYS_lremovexattr,
+        SysNo::Fremovexattr => libc::SYS_fremovexattr,
+        SysNo::Openat => libc::SYS_openat,
+        SysNo::Mkdirat => libc::SYS_mkdirat,
+        SysNo::Unlinkat => libc::SYS_unlinkat,
+        SysNo::Renameat2 => libc::SYS_renameat2,
+        SysNo::Linkat => libc::SYS_linkat,
+        SysNo::Symlinkat => libc::SYS_symlinkat,
+        SysNo::Fstatat => libc::SYS_newfstatat,
+        SysNo::Mmap => libc::SYS_mmap,
+        SysNo::Munmap => libc::SYS_munmap,
+        SysNo::Msync => libc::SYS_msync,
+        SysNo::Mremap => libc::SYS_mremap,
+        SysNo::Madvise => libc::SYS_madvise,
+        SysNo::Fallocate => libc::SYS_fallocate,
+        SysNo::CopyFileRange => libc::SYS_copy_file_range,
+        SysNo::SyncFileRange => libc::SYS_sync_file_range,
+        SysNo::Fadvise64 => libc::SYS_fadvise64,
+        SysNo::Readahead => libc::SYS_readahead,
+        SysNo::Close => libc::SYS_close,
+        SysNo::Dup => libc::SYS_dup,
+        SysNo::Dup2 => libc::SYS_dup2,
+        SysNo::Dup3 => libc::SYS_dup3,
+        SysNo::Chmod => libc::SYS_chmod,
+        SysNo::Fchmod => libc::SYS_fchmod,
+        SysNo::Fchmodat => libc::SYS_fchmodat,
+        SysNo::Chown => libc::SYS_chown,
+        SysNo::Fchown => libc::SYS_fchown,
+        SysNo::Lchown => libc::SYS_lchown,
+        SysNo::Umask => libc::SYS_umask,
+        SysNo::Utimensat => libc::SYS_utimensat,
+        SysNo::Mknod => libc::SYS_mknod,
+        SysNo::Mknodat => libc::SYS_mknodat,
+        SysNo::MemcpyToMap | SysNo::CrashPoint => return None,
+    };
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // umask is shared by the process and tests run in parallel
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    // a fresh directory for the program, removed again when dropped
+    struct Root(std::path::PathBuf);
+
+    impl Root {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "hdrepresentation-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Root {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn run(name: &str, prog: &Program) -> Vec<Option<SyscallResult>> {
+        let _lock = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let root = Root::new(name);
+        Executor::new(&root.0).unwrap().run(prog).unwrap()
+    }
+
+    // every result the model predicts is the one the kernel gave
+    fn check_against_model(name: &str, prog: &Program) {
+        let expected = FsModel::new().run(prog);
+        let results = run(name, prog);
+        for (pos, (result, expected)) in results.iter().zip(expected.iter()).enumerate() {
+            let result = result.unwrap_or_else(|| panic!("syscall {} was refused", pos));
+            assert!(
+                result.matches(expected),
+                "syscall {}: got {:?}, expected {}",
+                pos,
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn files_and_names_match_the_model() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        let fd = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.write(fd, Program::SRC8192, 100).unwrap();
+        prog.lseek(fd, 0, SEEK_SET).unwrap();
+        prog.read(fd, Program::DEST8192, 200).unwrap();
+        prog.mkdir("d", 0o755).unwrap();
+        prog.rename("a", "d/b").unwrap();
+        prog.open("a", O_RDONLY, 0).unwrap();
+        prog.unlink("d").unwrap();
+        prog.unlink("d/b").unwrap();
+        prog.close(fd).unwrap();
+        prog.rmdir("d").unwrap();
+        check_against_model("names", &prog);
+    }
+
+    #[test]
+    fn fd_numbers_match_a_c_run() {
+        let mut prog = Program::new();
+        let a = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        let b = prog.open("b", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.close(a).unwrap();
+        let c = prog.dup(b).unwrap();
+        prog.open("c", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.dup2(b, c).unwrap();
+        prog.close(c).unwrap();
+        prog.close(c).unwrap();
+        check_against_model("fds", &prog);
+        let results = run("fds", &prog);
+        let rets: Vec<i64> = results.iter().map(|x| x.unwrap().ret).collect();
+        assert_eq!(rets, vec![3, 4, 0, 3, 5, 3, 0, -1]);
+    }
+
+    #[test]
+    fn memcpy_past_the_end_of_the_address_space_is_refused() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        let fd = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.write(fd, Program::SRC8192, 4096).unwrap();
+        let base = prog
+            .mmap(fd, 4096, PROT_READ | PROT_WRITE, MAP_SHARED, 0)
+            .unwrap();
+        prog.memcpy_to_map(base, 0, Program::SRC8192, 16).unwrap();
+        prog.syscalls[3].args[1] = Arg::new(i64::MAX, false);
+        let results = run("memcpy", &prog);
+        assert!(results[2].is_some_and(|x| x.errno == 0));
+        assert_eq!(results[3], None);
+    }
+}
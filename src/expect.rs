@@ -0,0 +1,132 @@
+use crate::*;
+
+// first word of each line the checked C program prints for a mismatch
+pub const MISMATCH_TAG: &str = "mismatch";
+
+// a syscall whose result differs from what it expects. an expected value
+// of None was not checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Mismatch {
+    pub index: usize,
+    pub expected_ret: Option<i64>,
+    pub expected_errno: Option<i64>,
+    pub ret: i64,
+    pub errno: i64,
+}
+
+impl Mismatch {
+    // one report line, e.g.
+    // mismatch index=3 expected_ret=-1 expected_errno=2 ret=0 errno=0
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        if words.next()? != MISMATCH_TAG {
+            return None;
+        }
+        let mut field = |key: &str| -> Option<&str> {
+            let (k, v) = words.next()?.split_once('=')?;
+            (k == key).then_some(v)
+        };
+        let expected = |v: &str| match v {
+            "*" => Some(None),
+            _ => v.parse().ok().map(Some),
+        };
+        Some(Self {
+            index: field("index")?.parse().ok()?,
+            expected_ret: expected(field("expected_ret")?)?,
+            expected_errno: expected(field("expected_errno")?)?,
+            ret: field("ret")?.parse().ok()?,
+            errno: field("errno")?.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} index={} expected_ret={} expected_errno={} ret={} errno={}",
+            MISMATCH_TAG,
+            self.index,
+            show_expected(self.expected_ret),
+            show_expected(self.expected_errno),
+            self.ret,
+            self.errno
+        )
+    }
+}
+
+// the mismatches in the output of a checked program, other lines are skipped
+pub fn parse_mismatches(output: &str) -> Vec<Mismatch> {
+    output.lines().filter_map(Mismatch::parse).collect()
+}
+
+impl Syscall {
+    // check the parts of expected that are known
+    pub fn expect(&mut self, expected: Expected) {
+        self.expected_ret = expected.ret;
+        self.expected_errno = expected.errno;
+    }
+}
+
+impl Program {
+    // set the expected results of every syscall to what a fresh FsModel
+    // predicts, leaving unpredictable parts unchecked
+    pub fn expect_simulated(&mut self) {
+        let expected = FsModel::new().run(self);
+        for (s, e) in self.syscalls.iter_mut().zip(expected) {
+            s.expect(e);
+        }
+    }
+
+    // drop every expected result
+    pub fn clear_expected(&mut self) {
+        for s in self.syscalls.iter_mut() {
+            s.expect(Expected::UNKNOWN);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatch_lines_round_trip() {
+        let m = Mismatch {
+            index: 3,
+            expected_ret: Some(-1),
+            expected_errno: None,
+            ret: 0,
+            errno: 0,
+        };
+        let line = m.to_string();
+        assert_eq!(
+            line,
+            "mismatch index=3 expected_ret=-1 expected_errno=* ret=0 errno=0"
+        );
+        assert_eq!(Mismatch::parse(&line), Some(m));
+        let output = format!("some other output\n{}\nmismatch index=x\n", line);
+        assert_eq!(parse_mismatches(&output), vec![m]);
+    }
+
+    #[test]
+    fn checked_c_compares_simulated_results() {
+        let mut prog = Program::new();
+        prog.open("a", O_RDONLY, 0).unwrap();
+        prog.mkdir("d", 0o755).unwrap();
+        prog.expect_simulated();
+        assert_eq!(prog.syscalls[0].expected_ret, Some(-1));
+        assert_eq!(prog.syscalls[0].expected_errno, Some(ENOENT));
+        assert_eq!(prog.syscalls[1].expected_ret, Some(0));
+        let options = COptions {
+            check: true,
+            ..COptions::default()
+        };
+        let c = prog.try_to_c_with(&options).unwrap();
+        assert!(c.contains(&format!("__errno != {}", ENOENT)));
+        assert!(c.contains("mismatch index=1 expected_ret=0 expected_errno=0"));
+        prog.clear_expected();
+        let c = prog.try_to_c_with(&options).unwrap();
+        assert!(!c.contains(MISMATCH_TAG));
+    }
+}
@@ -2,8 +2,46 @@ pub use crate::types::*;
 pub use std::collections::HashMap;
 use std::hash::Hash;
 
+// name, value and the setxattr flags it was last set with
 #[derive(Serialize, Deserialize, Debug, Eq, Clone, Hash, PartialEq)]
-pub struct Xattr(pub String, pub String, pub i64);
+pub struct Xattr(
+    pub String,
+    #[serde(
+        serialize_with = "string_if_utf8",
+        deserialize_with = "bytes_or_string"
+    )]
+    pub Vec<u8>,
+    pub i64,
+);
+
+impl Xattr {
+    pub fn new(name: &str, value: &[u8], flags: i64) -> Self {
+        Self(String::from(name), value.to_vec(), flags)
+    }
+}
+
+// values used to be stored as strings, keep writing those when the value
+// is text so older readers can still load the program
+fn string_if_utf8<S: serde::Serializer>(value: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    match std::str::from_utf8(value) {
+        Ok(text) => s.serialize_str(text),
+        Err(_) => s.collect_seq(value),
+    }
+}
+
+// accept both the string and the byte array form
+fn bytes_or_string<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Bytes(Vec<u8>),
+        Str(String),
+    }
+    Ok(match Value::deserialize(d)? {
+        Value::Bytes(b) => b,
+        Value::Str(s) => s.into_bytes(),
+    })
+}
 
 /*#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(into = "Vec<(K, V)>")]
@@ -22,12 +60,30 @@ impl<K: Eq + Hash + Clone, V: Clone> From<Vec<(K, V)>> for ObjectMap<K, V>{
                             }
 }*/
 
+// expected inode metadata after the syscalls so far, None if unknown
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq, Hash, Default)]
+pub struct FileMeta {
+    pub mode: Option<i64>,
+    pub uid: Option<i64>,
+    pub gid: Option<i64>,
+    // (tv_sec, tv_nsec), tv_nsec may be UTIME_NOW
+    pub atime: Option<(i64, i64)>,
+    pub mtime: Option<(i64, i64)>,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq, Hash)]
 pub struct FileObject {
     pub rel_path: String,
     pub ftype: FileType,
     pub xattrs: Vec<Xattr>,
+    // xattrs the file had before the syscalls, set by hand or loaded with
+    // the program. None for programs saved before this was recorded, the
+    // xattrs are taken as the starting set then
+    #[serde(default)]
+    pub initial_xattrs: Option<Vec<Xattr>>,
     pub fd_index: i64,
+    #[serde(default)]
+    pub meta: FileMeta,
 }
 
 impl FileObject {
@@ -36,11 +92,24 @@ impl FileObject {
             rel_path: String::from(path),
             ftype,
             xattrs: Vec::<Xattr>::new(),
+            initial_xattrs: Some(Vec::<Xattr>::new()),
             fd_index,
+            meta: FileMeta::default(),
         }
     }
 }
 
+impl FileObject {
+    // xattr the file has before any syscall, kept when the xattrs are
+    // rebuilt from the syscalls
+    pub fn add_initial_xattr(&mut self, xattr: Xattr) {
+        self.initial_xattrs
+            .get_or_insert_with(Vec::<Xattr>::new)
+            .push(xattr.clone());
+        self.xattrs.push(xattr);
+    }
+}
+
 impl fmt::Display for FileObject {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut output = format!("Path {}\n Type: ", self.rel_path);
@@ -49,13 +118,29 @@ impl fmt::Display for FileObject {
             FileType::File => String::from("file"),
             FileType::Dir => String::from("dir"),
             FileType::Fifo => String::from("fifo"),
+            FileType::CharDev => String::from("char device"),
+            FileType::BlockDev => String::from("block device"),
+            FileType::Socket => String::from("socket"),
             _ => String::from("other"),
         };
         output.push_str(&ftype);
+        if let Some(mode) = self.meta.mode {
+            output.push_str(&format!("\nMode: {:o}", mode));
+        }
+        if let Some(uid) = self.meta.uid {
+            output.push_str(&format!("\nUid: {}", uid));
+        }
+        if let Some(gid) = self.meta.gid {
+            output.push_str(&format!("\nGid: {}", gid));
+        }
         output.push_str("\nXattrs:\n");
         for xattr in self.xattrs.iter() {
-            output.push_str(&format!("\t{}:{}\n", xattr.0, xattr.1));
+            output.push_str(&format!(
+                "\t{}:{}\n",
+                xattr.0,
+                String::from_utf8_lossy(&xattr.1)
+            ));
         }
-        write!(f, "{}\n", output)
+        writeln!(f, "{}", output)
     }
 }
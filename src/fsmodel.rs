@@ -0,0 +1,2141 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+// the directory the program runs in, paths above it are not modeled
+const ROOT: usize = 0;
+// description behind fds 0 to 2, which belong to whoever runs the program
+const STDIO: usize = usize::MAX;
+// contents of files larger than this are not tracked, only their size
+const MAX_TRACKED: i64 = 1 << 24;
+const MAXSYMLINKS: u32 = 40;
+const NAME_MAX: usize = 255;
+const PATH_MAX: usize = 4096;
+const OPEN_MAX: i64 = 1024;
+// bytes written to a struct stat
+const STAT_SIZE: i64 = 144;
+// offsetof(struct linux_dirent64, d_name)
+const DIRENT_NAME: i64 = 19;
+// largest count read and write transfer at once
+const MAX_RW_COUNT: i64 = 0x7ffff000;
+// top of the user address space mmap hands out addresses below
+const MMAP_LIMIT: i64 = 1 << 47;
+// files up to this size work on every filesystem, past it the limit differs
+const MAX_SAFE_SIZE: i64 = 1 << 31;
+
+// what a syscall is expected to return. ret is None if its value cannot be
+// predicted, errno is Some(0) if the syscall must succeed, both are None if
+// the outcome is unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Expected {
+    pub ret: Option<i64>,
+    pub errno: Option<i64>,
+}
+
+impl Expected {
+    pub const UNKNOWN: Expected = Expected {
+        ret: None,
+        errno: None,
+    };
+    // succeeds with a value that cannot be predicted, e.g. an address
+    pub const SUCCESS: Expected = Expected {
+        ret: None,
+        errno: Some(0),
+    };
+
+    pub fn ret(value: i64) -> Self {
+        Self {
+            ret: Some(value),
+            errno: Some(0),
+        }
+    }
+
+    pub fn errno(errno: i64) -> Self {
+        Self {
+            ret: Some(-1),
+            errno: Some(errno),
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.errno.is_some()
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.ret, self.errno) {
+            (_, None) => write!(f, "unknown"),
+            (None, Some(0)) => write!(f, "success"),
+            (Some(ret), Some(0)) => write!(f, "{}", ret),
+            (_, Some(errno)) => write!(f, "-1 errno {}", errno),
+        }
+    }
+}
+
+// Err carries any outcome that is not a predicted return value
+type Res<T> = Result<T, Expected>;
+
+fn err<T>(errno: i64) -> Res<T> {
+    Err(Expected::errno(errno))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inode {
+    pub ftype: FileType,
+    // permission bits
+    pub mode: i64,
+    pub uid: Option<i64>,
+    pub gid: Option<i64>,
+    // names linking to it, a directory only counts the one in its parent
+    pub nlink: i64,
+    pub size: i64,
+    // None once the contents cannot be predicted
+    pub data: Option<Vec<u8>>,
+    // target of a symlink
+    pub target: String,
+    // entries of a directory other than . and ..
+    pub entries: BTreeMap<String, usize>,
+    // directory holding a directory
+    pub parent: usize,
+    pub xattrs: Vec<Xattr>,
+    // false once an xattr syscall had an effect that cannot be predicted
+    pub xattrs_known: bool,
+    // bumped whenever the entries change
+    version: u64,
+}
+
+impl Inode {
+    fn new(ftype: FileType, mode: i64, parent: usize) -> Self {
+        Self {
+            ftype,
+            mode,
+            uid: None,
+            gid: None,
+            nlink: 0,
+            size: 0,
+            data: Some(Vec::<u8>::new()),
+            target: String::new(),
+            entries: BTreeMap::new(),
+            parent,
+            xattrs: Vec::<Xattr>::new(),
+            xattrs_known: true,
+            version: 0,
+        }
+    }
+
+    fn resize(&mut self, size: i64) {
+        if size > MAX_TRACKED {
+            self.data = None;
+        }
+        if let Some(d) = &mut self.data {
+            d.resize(size as usize, 0);
+        }
+        self.size = size;
+    }
+
+    // bytes None means the written contents are not known
+    fn write_at(&mut self, offset: i64, bytes: Option<Vec<u8>>, n: i64) {
+        if offset + n > self.size {
+            self.resize(offset + n);
+        }
+        match (&mut self.data, bytes) {
+            (Some(d), Some(b)) => {
+                d[offset as usize..(offset + n) as usize].copy_from_slice(&b[..n as usize])
+            }
+            _ => self.data = None,
+        }
+    }
+
+    // n bytes from offset, which must lie within the file
+    fn read_at(&self, offset: i64, n: i64) -> Option<Vec<u8>> {
+        self.data.as_ref().map(|d| match n > 0 {
+            true => d[offset as usize..(offset + n) as usize].to_vec(),
+            false => Vec::new(),
+        })
+    }
+
+    // zero the part of [offset, end) that lies within the file
+    fn zero(&mut self, offset: i64, end: i64) {
+        let end = end.min(self.size);
+        if let Some(d) = &mut self.data {
+            if offset < end {
+                d[offset as usize..end as usize].fill(0);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenFile {
+    pub ino: usize,
+    pub offset: i64,
+    pub flags: i64,
+    // version of the directory when getdents returned all of it
+    listed: Option<u64>,
+}
+
+impl OpenFile {
+    fn readable(&self) -> bool {
+        self.flags & 3 != O_WRONLY
+    }
+
+    fn writable(&self) -> bool {
+        self.flags & 3 != O_RDONLY
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mapping {
+    // None for an anonymous mapping
+    ino: Option<usize>,
+    offset: i64,
+    // rounded up to whole pages
+    len: i64,
+    // bytes at the start that were unmapped since
+    hole: i64,
+    shared: bool,
+    writable: bool,
+}
+
+// where the path of a syscall led
+struct Lookup {
+    parent: usize,
+    name: String,
+    ino: Option<usize>,
+    // the path ended in a slash
+    dir_only: bool,
+}
+
+// in-memory POSIX filesystem that interprets the syscalls of a program and
+// predicts their results. it assumes the program starts in an empty
+// directory as root, on a filesystem with user and trusted xattrs and no
+// security labels; anything else it cannot tell makes the result unknown
+#[derive(Debug, Clone)]
+pub struct FsModel {
+    // inodes[0] is the working directory
+    pub inodes: Vec<Inode>,
+    // fd number to index into files
+    pub fds: BTreeMap<i64, usize>,
+    pub files: Vec<OpenFile>,
+    pub umask: i64,
+    // xattr bytes a file holds before the filesystem may refuse more
+    pub xattr_space: usize,
+    // set once a syscall had an effect the model cannot predict, every
+    // result after it is unknown
+    pub diverged: bool,
+    // value of each variable at run time, None if unknown
+    values: Vec<Option<i64>>,
+    // capacity and contents of each byte buffer variable
+    caps: Vec<i64>,
+    bufs: Vec<Option<Vec<u8>>>,
+    maps: HashMap<usize, Mapping>,
+}
+
+impl Default for FsModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FsModel {
+    pub fn new() -> Self {
+        let mut root = Inode::new(FileType::Dir, 0o755, ROOT);
+        root.nlink = 1;
+        Self {
+            inodes: vec![root],
+            fds: (0..3).map(|x| (x, STDIO)).collect(),
+            files: Vec::<OpenFile>::new(),
+            umask: 0o022,
+            xattr_space: 4000,
+            diverged: false,
+            values: Vec::new(),
+            caps: Vec::new(),
+            bufs: Vec::new(),
+            maps: HashMap::new(),
+        }
+    }
+
+    // interpret the syscalls of prog in order, one Expected per syscall
+    pub fn run(&mut self, prog: &Program) -> Vec<Expected> {
+        self.start(prog);
+        prog.syscalls.iter().map(|s| self.step(prog, s)).collect()
+    }
+
+    // take the initial values of the variables of prog, before stepping
+    // through its syscalls
+    pub fn start(&mut self, prog: &Program) {
+        self.values.clear();
+        self.caps.clear();
+        self.bufs.clear();
+        self.add_variables(prog);
+    }
+
+    // take the initial values of the variables prog declared since, so
+    // stepping can go on after prog grew
+    pub fn add_variables(&mut self, prog: &Program) {
+        let new = prog.variables.get(self.values.len()..).unwrap_or_default();
+        self.values.extend(new.iter().map(|v| match &*v.var_type {
+            VariableType::Long(x) => Some(*x),
+            VariableType::VoidPtr => Some(0),
+            // MAP_FAILED
+            VariableType::MmapBase => Some(-1),
+            _ => None,
+        }));
+        self.caps.extend(new.iter().map(|v| match &*v.var_type {
+            VariableType::UCharPtr(_, size) => *size as i64,
+            VariableType::Str(s) => s.len() as i64 + 1,
+            _ => 0,
+        }));
+        self.bufs.extend(new.iter().map(|v| match &*v.var_type {
+            VariableType::UCharPtr(value, size) => {
+                let mut b = value.clone().unwrap_or_default();
+                b.resize(*size as usize, 0);
+                Some(b)
+            }
+            VariableType::Str(s) => {
+                let mut b = s.clone().into_bytes();
+                b.push(0);
+                Some(b)
+            }
+            _ => None,
+        }));
+    }
+
+    // interpret one syscall
+    pub fn step(&mut self, prog: &Program, s: &Syscall) -> Expected {
+        if self.diverged {
+            return Expected::UNKNOWN;
+        }
+        let expected = match self.apply(prog, s) {
+            Ok(ret) => Expected::ret(ret),
+            Err(e) => e,
+        };
+        if !expected.is_known() && !is_query(s.nr) {
+            self.diverged = true;
+        }
+        if s.ret_index != -1 {
+            if let Some(v) = self.values.get_mut(s.ret_index as usize) {
+                *v = expected.ret;
+            }
+        }
+        expected
+    }
+
+    // type of the file open as fd
+    pub fn fd_type(&self, fd: i64) -> Option<FileType> {
+        let f = self.files.get(*self.fds.get(&fd)?)?;
+        Some(self.inodes[f.ino].ftype)
+    }
+
+    // inode of the file the msync s writes back in full, a shared mapping
+    // from the start of the file that s covers up to its end
+    pub fn msynced_file(&self, s: &Syscall) -> Option<usize> {
+        let map = &self.maps[&self.map_arg(s, 0).ok()??];
+        let ino = map.ino?;
+        let end = page_round(self.val(s, 1).ok()?)?;
+        (map.shared && map.hole == 0 && map.offset == 0 && end >= self.inodes[ino].size)
+            .then_some(ino)
+    }
+
+    // the open file behind the fd argument k of s
+    pub fn open_file(&self, s: &Syscall, k: usize) -> Option<&OpenFile> {
+        self.file(s, k).ok().map(|f| &self.files[f])
+    }
+
+    // links of the inode, counting . and the .. of subdirectories
+    pub fn nlink(&self, ino: usize) -> i64 {
+        let inode = &self.inodes[ino];
+        if inode.ftype != FileType::Dir || inode.nlink == 0 {
+            return inode.nlink;
+        }
+        let subdirs = inode
+            .entries
+            .values()
+            .filter(|x| self.inodes[**x].ftype == FileType::Dir)
+            .count();
+        2 + subdirs as i64
+    }
+
+    // inode at path relative to the working directory, symlinks followed
+    pub fn find(&self, path: &str) -> Option<usize> {
+        let mut links = 0;
+        self.walk(ROOT, path, true, &mut links).ok()?.ino
+    }
+
+    // every path in the tree with its inode, parents before children
+    pub fn paths(&self) -> Vec<(String, usize)> {
+        let mut paths = Vec::<(String, usize)>::new();
+        let mut todo = vec![(String::new(), ROOT)];
+        while let Some((prefix, dir)) = todo.pop() {
+            for (name, ino) in self.inodes[dir].entries.iter() {
+                let path = match prefix.is_empty() {
+                    true => name.clone(),
+                    false => format!("{}/{}", prefix, name),
+                };
+                paths.push((path.clone(), *ino));
+                if self.inodes[*ino].ftype == FileType::Dir {
+                    todo.push((path, *ino));
+                }
+            }
+        }
+        paths
+    }
+
+    fn apply(&mut self, prog: &Program, s: &Syscall) -> Res<i64> {
+        let path = |k: usize| prog.arg_str(s.args.get(k)).ok_or(Expected::UNKNOWN);
+        match s.nr {
+            SysNo::Open => {
+                let flags = self.val(s, 1)?;
+                check_open_flags(flags)?;
+                self.open(ROOT, &path(0)?, flags, self.val(s, 2)?)
+            }
+            SysNo::Openat => {
+                let flags = self.val(s, 2)?;
+                check_open_flags(flags)?;
+                let dir = self.at_dir(s, 0)?;
+                self.open(dir, &path(1)?, flags, self.val(s, 3)?)
+            }
+            SysNo::Read => self.read(prog, s, None),
+            SysNo::Pread => self.read(prog, s, Some(self.val(s, 3)?)),
+            SysNo::Write => self.write(s, None),
+            SysNo::Pwrite => self.write(s, Some(self.val(s, 3)?)),
+            SysNo::Lseek => self.lseek(s),
+            SysNo::Getdents => self.getdents(prog, s),
+            SysNo::Fstat => {
+                self.file(s, 0)?;
+                self.clobber(prog, s, 1, STAT_SIZE)
+            }
+            SysNo::Stat | SysNo::Lstat => {
+                let l = self.lookup(ROOT, &path(0)?, s.nr == SysNo::Stat)?;
+                self.existing(&l)?;
+                self.clobber(prog, s, 1, STAT_SIZE)
+            }
+            SysNo::Fstatat => {
+                let flags = self.val(s, 3)?;
+                let valid =
+                    AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH | AT_NO_AUTOMOUNT | AT_STATX_SYNC_TYPE;
+                if flags & !valid != 0 {
+                    return err(EINVAL);
+                }
+                self.at_target(s, 0, &path(1)?, flags)?;
+                self.clobber(prog, s, 2, STAT_SIZE)
+            }
+            SysNo::Access => {
+                let mode = self.val(s, 1)?;
+                if mode & !(R_OK | W_OK | X_OK) != 0 {
+                    return err(EINVAL);
+                }
+                let l = self.lookup(ROOT, &path(0)?, true)?;
+                let ino = self.existing(&l)?;
+                let inode = &self.inodes[ino];
+                // root may execute anything some execute bit is set on
+                if mode & X_OK != 0 && inode.ftype != FileType::Dir && inode.mode & 0o111 == 0 {
+                    return err(EACCES);
+                }
+                Ok(0)
+            }
+            SysNo::Rename => {
+                let lo = self.lookup_entry(ROOT, &path(0)?)?;
+                let ln = self.lookup_entry(ROOT, &path(1)?)?;
+                self.rename(lo, ln, 0)
+            }
+            SysNo::Renameat2 => {
+                let flags = self.val(s, 4)?;
+                if flags & !(RENAME_NOREPLACE | RENAME_EXCHANGE | RENAME_WHITEOUT) != 0
+                    || flags & RENAME_NOREPLACE != 0 && flags & RENAME_EXCHANGE != 0
+                {
+                    return err(EINVAL);
+                }
+                if flags & RENAME_WHITEOUT != 0 {
+                    return Err(Expected::UNKNOWN);
+                }
+                // the old parent is looked up before the new dir fd is checked
+                let lo = self.lookup_entry(self.at_dir(s, 0)?, &path(1)?)?;
+                let ln = self.lookup_entry(self.at_dir(s, 2)?, &path(3)?)?;
+                self.rename(lo, ln, flags)
+            }
+            SysNo::Fsync | SysNo::Fdatasync => {
+                let f = self.file(s, 0)?;
+                match self.inodes[self.files[f].ino].ftype {
+                    FileType::Fifo | FileType::Socket => err(EINVAL),
+                    _ => Ok(0),
+                }
+            }
+            SysNo::Syncfs => self.file(s, 0).map(|_| 0),
+            SysNo::Sendfile => self.sendfile(s),
+            SysNo::Truncate => {
+                let length = self.val(s, 1)?;
+                if length < 0 {
+                    return err(EINVAL);
+                }
+                let l = self.lookup(ROOT, &path(0)?, true)?;
+                let ino = self.existing(&l)?;
+                match self.inodes[ino].ftype {
+                    FileType::Dir => err(EISDIR),
+                    FileType::File => self.truncate(ino, length),
+                    _ => err(EINVAL),
+                }
+            }
+            SysNo::Ftruncate => {
+                let length = self.val(s, 1)?;
+                if length < 0 {
+                    return err(EINVAL);
+                }
+                let f = self.file(s, 0)?;
+                let ino = self.files[f].ino;
+                if !self.files[f].writable() || self.inodes[ino].ftype != FileType::File {
+                    return err(EINVAL);
+                }
+                self.truncate(ino, length)
+            }
+            SysNo::Mkdir => self.mkdir(ROOT, &path(0)?, self.val(s, 1)?),
+            SysNo::Mkdirat => {
+                let dir = self.at_dir(s, 0)?;
+                self.mkdir(dir, &path(1)?, self.val(s, 2)?)
+            }
+            SysNo::Rmdir => self.rmdir(ROOT, &path(0)?),
+            SysNo::Unlink => self.unlink(ROOT, &path(0)?),
+            SysNo::Unlinkat => match self.val(s, 2)? {
+                0 => self.unlink(self.at_dir(s, 0)?, &path(1)?),
+                AT_REMOVEDIR => self.rmdir(self.at_dir(s, 0)?, &path(1)?),
+                _ => err(EINVAL),
+            },
+            SysNo::Link => {
+                let ino = self.existing(&self.lookup(ROOT, &path(0)?, false)?)?;
+                let ln = self.lookup_entry(ROOT, &path(1)?)?;
+                self.link(ino, ln)
+            }
+            SysNo::Linkat => {
+                let flags = self.val(s, 4)?;
+                if flags & !(AT_SYMLINK_FOLLOW | AT_EMPTY_PATH) != 0 {
+                    return err(EINVAL);
+                }
+                if flags & AT_EMPTY_PATH != 0 {
+                    return Err(Expected::UNKNOWN);
+                }
+                let follow = flags & AT_SYMLINK_FOLLOW != 0;
+                let lo = self.lookup(self.at_dir(s, 0)?, &path(1)?, follow)?;
+                // the old path is resolved before the new dir fd is checked
+                let ino = self.existing(&lo)?;
+                let ln = self.lookup_entry(self.at_dir(s, 2)?, &path(3)?)?;
+                self.link(ino, ln)
+            }
+            SysNo::Symlink => self.symlink(&path(0)?, ROOT, &path(1)?),
+            SysNo::Symlinkat => {
+                let dir = self.at_dir(s, 1)?;
+                self.symlink(&path(0)?, dir, &path(2)?)
+            }
+            // the name and value are copied in before the file is looked up
+            SysNo::Setxattr | SysNo::Lsetxattr | SysNo::Fsetxattr => {
+                let name = path(1)?;
+                let size = self.val(s, 3)?;
+                let flags = self.val(s, 4)?;
+                if flags & !(XATTR_CREATE | XATTR_REPLACE) != 0 {
+                    return err(EINVAL);
+                }
+                check_xattr_len(&name)?;
+                if size as u64 > XATTR_SIZE_MAX as u64 {
+                    return err(E2BIG);
+                }
+                let value = self.load(s, 2, size)?;
+                let ino = self.xattr_target(prog, s)?;
+                self.set_xattr(ino, &name, value, flags)
+            }
+            SysNo::Getxattr | SysNo::Lgetxattr | SysNo::Fgetxattr => {
+                let name = path(1)?;
+                check_xattr_len(&name)?;
+                let ino = self.xattr_target(prog, s)?;
+                self.get_xattr(prog, s, ino, &name)
+            }
+            SysNo::Removexattr | SysNo::Lremovexattr | SysNo::Fremovexattr => {
+                let name = path(1)?;
+                check_xattr_len(&name)?;
+                let ino = self.xattr_target(prog, s)?;
+                self.remove_xattr(ino, &name)
+            }
+            SysNo::Listxattr | SysNo::Llistxattr | SysNo::Flistxattr => {
+                let ino = self.xattr_target(prog, s)?;
+                self.list_xattr(prog, s, ino)
+            }
+            SysNo::Mmap => self.mmap(s),
+            SysNo::Munmap => {
+                let length = self.val(s, 1)?;
+                if length <= 0 {
+                    return err(EINVAL);
+                }
+                let m = self.map_arg(s, 0)?.ok_or(Expected::errno(EINVAL))?;
+                // a length that wraps when rounded to pages is refused
+                let length = page_round(length).ok_or(Expected::errno(EINVAL))?;
+                let map = self.maps.get_mut(&m).ok_or(Expected::UNKNOWN)?;
+                map.hole = map.hole.max(length.min(map.len));
+                Ok(0)
+            }
+            SysNo::Msync => {
+                let flags = self.val(s, 2)?;
+                if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+                    || flags & MS_ASYNC != 0 && flags & MS_SYNC != 0
+                {
+                    return err(EINVAL);
+                }
+                self.map_range(s)
+            }
+            SysNo::Madvise => self.madvise(s),
+            SysNo::Mremap => self.mremap(s),
+            SysNo::MemcpyToMap => self.memcpy_to_map(s),
+            // the program goes on unless the hook ends it
+            SysNo::CrashPoint => Ok(0),
+            SysNo::Fallocate => self.fallocate(s),
+            SysNo::CopyFileRange => self.copy_file_range(s),
+            SysNo::SyncFileRange => {
+                let f = self.file(s, 0)?;
+                let offset = self.val(s, 1)?;
+                let nbytes = self.val(s, 2)?;
+                let flags = self.val(s, 3)?;
+                if flags
+                    & !(SYNC_FILE_RANGE_WAIT_BEFORE
+                        | SYNC_FILE_RANGE_WRITE
+                        | SYNC_FILE_RANGE_WAIT_AFTER)
+                    != 0
+                    || offset < 0
+                    || nbytes < 0
+                    || offset.checked_add(nbytes).is_none()
+                {
+                    return err(EINVAL);
+                }
+                match self.inodes[self.files[f].ino].ftype {
+                    FileType::File | FileType::Dir | FileType::Symlink | FileType::BlockDev => {
+                        Ok(0)
+                    }
+                    _ => err(ESPIPE),
+                }
+            }
+            SysNo::Fadvise64 => {
+                let f = self.file(s, 0)?;
+                if self.inodes[self.files[f].ino].ftype == FileType::Fifo {
+                    return err(ESPIPE);
+                }
+                let advice = self.val(s, 3)?;
+                if self.val(s, 2)? < 0
+                    || !(POSIX_FADV_NORMAL..=POSIX_FADV_NOREUSE).contains(&advice)
+                {
+                    return err(EINVAL);
+                }
+                Ok(0)
+            }
+            SysNo::Readahead => {
+                let f = self.file(s, 0)?;
+                if !self.files[f].readable() {
+                    return err(EBADF);
+                }
+                match self.inodes[self.files[f].ino].ftype {
+                    // fadvise refuses a negative offset or length
+                    FileType::File if self.val(s, 1)? >= 0 && self.val(s, 2)? >= 0 => Ok(0),
+                    _ => err(EINVAL),
+                }
+            }
+            SysNo::Close => {
+                self.file(s, 0)?;
+                self.fds.remove(&self.val(s, 0)?);
+                Ok(0)
+            }
+            SysNo::Dup => {
+                let f = self.file(s, 0)?;
+                self.new_fd(f)
+            }
+            SysNo::Dup2 | SysNo::Dup3 => {
+                let old = self.val(s, 0)?;
+                let new = self.val(s, 1)?;
+                if s.nr == SysNo::Dup3 && (self.val(s, 2)? & !O_CLOEXEC != 0 || old == new) {
+                    return err(EINVAL);
+                }
+                if old != new && !(0..OPEN_MAX).contains(&new) {
+                    return err(EBADF);
+                }
+                let f = self.file(s, 0)?;
+                if old != new {
+                    self.fds.insert(new, f);
+                }
+                Ok(new)
+            }
+            SysNo::Chmod | SysNo::Fchmod | SysNo::Fchmodat => {
+                let (ino, mode) = match s.nr {
+                    SysNo::Chmod => {
+                        let l = self.lookup(ROOT, &path(0)?, true)?;
+                        (self.existing(&l)?, self.val(s, 1)?)
+                    }
+                    SysNo::Fchmod => (self.files[self.file(s, 0)?].ino, self.val(s, 1)?),
+                    _ => (self.at_target(s, 0, &path(1)?, 0)?, self.val(s, 2)?),
+                };
+                self.inodes[ino].mode = mode & 0o7777;
+                Ok(0)
+            }
+            SysNo::Chown | SysNo::Lchown | SysNo::Fchown => {
+                let ino = match s.nr {
+                    SysNo::Fchown => self.files[self.file(s, 0)?].ino,
+                    _ => {
+                        let l = self.lookup(ROOT, &path(0)?, s.nr == SysNo::Chown)?;
+                        self.existing(&l)?
+                    }
+                };
+                let uid = self.val(s, 1)?;
+                let gid = self.val(s, 2)?;
+                let inode = &mut self.inodes[ino];
+                if uid != -1 {
+                    inode.uid = Some(uid);
+                }
+                if gid != -1 {
+                    inode.gid = Some(gid);
+                }
+                // even root clears the set-id bits of what it chowns
+                if inode.ftype != FileType::Dir {
+                    inode.mode &= !S_ISUID;
+                    if inode.mode & 0o010 != 0 {
+                        inode.mode &= !S_ISGID;
+                    }
+                }
+                Ok(0)
+            }
+            SysNo::Umask => {
+                let old = self.umask;
+                self.umask = self.val(s, 0)? & 0o777;
+                Ok(old)
+            }
+            SysNo::Utimensat => self.utimensat(prog, s),
+            SysNo::Mknod => self.mknod(ROOT, &path(0)?, self.val(s, 1)?),
+            SysNo::Mknodat => {
+                let dir = self.at_dir(s, 0)?;
+                self.mknod(dir, &path(1)?, self.val(s, 2)?)
+            }
+        }
+    }
+
+    // run-time value of argument k
+    fn val(&self, s: &Syscall, k: usize) -> Res<i64> {
+        let arg = s.args.get(k).ok_or(Expected::UNKNOWN)?;
+        let value = match arg.is_variable {
+            true => arg
+                .index
+                .and_then(|i| self.values.get(i))
+                .copied()
+                .flatten(),
+            false => arg.value,
+        };
+        let value = value.ok_or(Expected::UNKNOWN)?;
+        let int = i32::MIN as i64..=i32::MAX as i64;
+        match signature(s.nr).get(k) {
+            // a negative literal is a C int, how it widens to a 64-bit offset
+            // or length is up to the compiler
+            Some(ArgKind::Offset | ArgKind::Length) if !arg.is_variable && value < 0 => {
+                match int.contains(&value) {
+                    true => Err(Expected::UNKNOWN),
+                    false => Ok(value),
+                }
+            }
+            // mmap and mremap take their flags as unsigned long
+            Some(ArgKind::Flags) if matches!(s.nr, SysNo::Mmap | SysNo::Mremap) => {
+                match int.contains(&value) {
+                    true => Ok(value),
+                    false => Err(Expected::UNKNOWN),
+                }
+            }
+            // umode_t
+            Some(ArgKind::Mode) if !matches!(s.nr, SysNo::Access | SysNo::Umask) => {
+                Ok(value & 0xffff)
+            }
+            // the kernel only looks at the low 32 bits
+            Some(
+                ArgKind::Fd
+                | ArgKind::DirFd
+                | ArgKind::Flags
+                | ArgKind::FallocMode
+                | ArgKind::Mode
+                | ArgKind::Uid
+                | ArgKind::Gid
+                | ArgKind::Dev,
+            ) => Ok(value as i32 as i64),
+            _ => Ok(value),
+        }
+    }
+
+    // open file description behind the fd passed as argument k
+    fn file(&self, s: &Syscall, k: usize) -> Res<usize> {
+        self.file_of(self.val(s, k)?)
+    }
+
+    fn file_of(&self, fd: i64) -> Res<usize> {
+        match self.fds.get(&fd) {
+            None => err(EBADF),
+            Some(&STDIO) => Err(Expected::UNKNOWN),
+            Some(f) => Ok(*f),
+        }
+    }
+
+    // lowest free fd, referring to the open file description f
+    fn new_fd(&mut self, f: usize) -> Res<i64> {
+        let fd = (0..)
+            .find(|x| !self.fds.contains_key(x))
+            .unwrap_or(OPEN_MAX);
+        if fd >= OPEN_MAX {
+            return Err(Expected::UNKNOWN);
+        }
+        self.fds.insert(fd, f);
+        Ok(fd)
+    }
+
+    // directory the *at syscall resolves relative paths from
+    fn at_dir(&self, s: &Syscall, k: usize) -> Res<usize> {
+        let dirfd = self.val(s, k)?;
+        if dirfd == AT_FDCWD {
+            return Ok(ROOT);
+        }
+        Ok(self.files[self.file_of(dirfd)?].ino)
+    }
+
+    // inode an *at syscall with flags operates on
+    fn at_target(&self, s: &Syscall, k: usize, path: &str, flags: i64) -> Res<usize> {
+        let dir = self.at_dir(s, k)?;
+        if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+            return Ok(dir);
+        }
+        let l = self.lookup(dir, path, flags & AT_SYMLINK_NOFOLLOW == 0)?;
+        self.existing(&l)
+    }
+
+    // resolve path from dir. a trailing slash follows a final symlink as well
+    fn lookup(&self, dir: usize, path: &str, follow: bool) -> Res<Lookup> {
+        let mut links = 0;
+        self.walk(dir, path, follow, &mut links)
+    }
+
+    // resolve path from dir without following a final symlink, for the
+    // syscalls that add or remove the entry itself
+    fn lookup_entry(&self, dir: usize, path: &str) -> Res<Lookup> {
+        let trimmed = path.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return self.lookup(dir, path, false);
+        }
+        let mut l = self.lookup(dir, trimmed, false)?;
+        l.dir_only = trimmed.len() != path.len();
+        Ok(l)
+    }
+
+    fn walk(&self, dir: usize, path: &str, follow: bool, links: &mut u32) -> Res<Lookup> {
+        if path.is_empty() {
+            return err(ENOENT);
+        }
+        if path.starts_with('/') {
+            return Err(Expected::UNKNOWN);
+        }
+        if path.len() >= PATH_MAX {
+            return err(ENAMETOOLONG);
+        }
+        let dir_only = path.ends_with('/');
+        let names: Vec<&str> = path.split('/').filter(|x| !x.is_empty()).collect();
+        let mut dir = dir;
+        for (i, name) in names.iter().enumerate() {
+            if self.inodes[dir].ftype != FileType::Dir {
+                return err(ENOTDIR);
+            }
+            if name.len() > NAME_MAX {
+                return err(ENAMETOOLONG);
+            }
+            let ino = match *name {
+                "." => Some(dir),
+                // leaves the working directory
+                ".." if dir == ROOT => return Err(Expected::UNKNOWN),
+                ".." => Some(self.inodes[dir].parent),
+                _ => self.inodes[dir].entries.get(*name).copied(),
+            };
+            let link = ino.filter(|x| self.inodes[*x].ftype == FileType::Symlink);
+            if i + 1 == names.len() {
+                return match link {
+                    Some(x) if follow || dir_only => {
+                        let mut l = self.follow(dir, x, links)?;
+                        l.dir_only |= dir_only;
+                        Ok(l)
+                    }
+                    // nothing can be created in a removed directory
+                    _ if ino.is_none() && self.inodes[dir].nlink == 0 => err(ENOENT),
+                    _ => Ok(Lookup {
+                        parent: dir,
+                        name: String::from(*name),
+                        ino,
+                        dir_only,
+                    }),
+                };
+            }
+            dir = match link {
+                Some(x) => self
+                    .follow(dir, x, links)?
+                    .ino
+                    .ok_or(Expected::errno(ENOENT))?,
+                None => ino.ok_or(Expected::errno(ENOENT))?,
+            };
+        }
+        Err(Expected::UNKNOWN)
+    }
+
+    fn follow(&self, dir: usize, link: usize, links: &mut u32) -> Res<Lookup> {
+        *links += 1;
+        if *links > MAXSYMLINKS {
+            return err(ELOOP);
+        }
+        self.walk(dir, &self.inodes[link].target, true, links)
+    }
+
+    // the inode the lookup found
+    fn existing(&self, l: &Lookup) -> Res<usize> {
+        let ino = l.ino.ok_or(Expected::errno(ENOENT))?;
+        if l.dir_only && self.inodes[ino].ftype != FileType::Dir {
+            return err(ENOTDIR);
+        }
+        Ok(ino)
+    }
+
+    fn add_entry(&mut self, dir: usize, name: &str, ino: usize) {
+        self.inodes[dir].entries.insert(String::from(name), ino);
+        self.inodes[dir].version += 1;
+        self.inodes[ino].nlink += 1;
+        if self.inodes[ino].ftype == FileType::Dir {
+            self.inodes[ino].parent = dir;
+        }
+    }
+
+    fn remove_entry(&mut self, dir: usize, name: &str) {
+        if let Some(ino) = self.inodes[dir].entries.remove(name) {
+            self.inodes[dir].version += 1;
+            self.inodes[ino].nlink -= 1;
+        }
+    }
+
+    // new inode named by the lookup, mode is final
+    fn create(&mut self, l: &Lookup, ftype: FileType, mode: i64) -> usize {
+        let ino = self.inodes.len();
+        let mut inode = Inode::new(ftype, mode, l.parent);
+        // a directory with the set-group-ID bit passes on its group, and the
+        // bit to subdirectories
+        let parent = &self.inodes[l.parent];
+        if parent.mode & S_ISGID != 0 {
+            inode.gid = parent.gid;
+            if ftype == FileType::Dir {
+                inode.mode |= S_ISGID;
+            }
+        }
+        self.inodes.push(inode);
+        self.add_entry(l.parent, &l.name, ino);
+        ino
+    }
+
+    // whether dir is x or one of its ancestors
+    fn is_ancestor(&self, dir: usize, x: usize) -> bool {
+        let mut x = x;
+        loop {
+            if x == dir {
+                return true;
+            }
+            if x == ROOT {
+                return false;
+            }
+            x = self.inodes[x].parent;
+        }
+    }
+
+    // flags passed check_open_flags
+    fn open(&mut self, dir: usize, path: &str, flags: i64, mode: i64) -> Res<i64> {
+        let creat = flags & O_CREAT != 0;
+        let excl = creat && flags & O_EXCL != 0;
+        let l = self.lookup(dir, path, !excl && flags & O_NOFOLLOW == 0)?;
+        let ino = match l.ino {
+            Some(_) if excl => return err(EEXIST),
+            Some(ino) => {
+                let ftype = self.inodes[ino].ftype;
+                if ftype == FileType::Symlink {
+                    return err(ELOOP);
+                }
+                if ftype != FileType::Dir && (l.dir_only || flags & O_DIRECTORY != 0) {
+                    return err(ENOTDIR);
+                }
+                let reader = self
+                    .fds
+                    .values()
+                    .filter(|x| **x != STDIO)
+                    .any(|x| self.files[*x].ino == ino && self.files[*x].readable());
+                match ftype {
+                    // O_TRUNC asks for write access as well
+                    FileType::Dir if creat || flags & (3 | O_TRUNC) != O_RDONLY => {
+                        return err(EISDIR)
+                    }
+                    // opening without O_NONBLOCK waits for the other end
+                    FileType::Fifo if flags & O_NONBLOCK == 0 => return Err(Expected::UNKNOWN),
+                    FileType::Fifo if flags & 3 == O_WRONLY && !reader => return err(ENXIO),
+                    FileType::Socket => return err(ENXIO),
+                    // whether a driver serves the device depends on the machine
+                    FileType::CharDev | FileType::BlockDev => return Err(Expected::UNKNOWN),
+                    // only regular files do direct io
+                    _ if flags & O_DIRECT != 0 && ftype != FileType::File => return err(EINVAL),
+                    FileType::File if flags & O_TRUNC != 0 => self.inodes[ino].resize(0),
+                    _ => {}
+                }
+                ino
+            }
+            None if !creat => return err(ENOENT),
+            None if l.dir_only => return err(EISDIR),
+            None => self.create(&l, FileType::File, mode & 0o7777 & !self.umask),
+        };
+        self.files.push(OpenFile {
+            ino,
+            offset: 0,
+            flags,
+            listed: None,
+        });
+        self.new_fd(self.files.len() - 1)
+    }
+
+    // the regular file behind the fd passed as argument 0, for read and write
+    fn io_file(&self, s: &Syscall, write: bool, at: Option<i64>) -> Res<usize> {
+        if at.is_some_and(|x| x < 0) {
+            return err(EINVAL);
+        }
+        let f = self.file(s, 0)?;
+        let file = &self.files[f];
+        let ftype = self.inodes[file.ino].ftype;
+        if at.is_some() && matches!(ftype, FileType::Fifo | FileType::Socket) {
+            return err(ESPIPE);
+        }
+        if write && !file.writable() || !write && !file.readable() {
+            return err(EBADF);
+        }
+        match ftype {
+            // the offset getdents left is a cookie of the filesystem, which
+            // read checks before it fails
+            FileType::Dir if at.is_none() && file.listed.is_some() => Err(Expected::UNKNOWN),
+            FileType::Dir => err(EISDIR),
+            // direct io has alignment rules of the filesystem
+            FileType::File if file.flags & O_DIRECT == 0 => Ok(f),
+            // pipes block or return what the other end wrote
+            _ => Err(Expected::UNKNOWN),
+        }
+    }
+
+    // byte buffer variable passed as argument k, None for NULL
+    fn buf_arg(&self, s: &Syscall, k: usize) -> Res<Option<usize>> {
+        let arg = s.args.get(k).ok_or(Expected::UNKNOWN)?;
+        if !arg.is_variable {
+            return match arg.value {
+                Some(0) => Ok(None),
+                _ => Err(Expected::UNKNOWN),
+            };
+        }
+        match arg.index {
+            Some(i) if self.caps.get(i).is_some_and(|x| *x > 0) => Ok(Some(i)),
+            // a mapping or something else the model does not track
+            _ => Err(Expected::UNKNOWN),
+        }
+    }
+
+    // contents of the first n bytes of the buffer passed as argument k
+    fn load(&self, s: &Syscall, k: usize, n: i64) -> Res<Option<Vec<u8>>> {
+        if n == 0 {
+            return Ok(Some(Vec::new()));
+        }
+        let buf = self.buf_arg(s, k)?.ok_or(Expected::errno(EFAULT))?;
+        if n < 0 || n > self.caps[buf] {
+            return Err(Expected::UNKNOWN);
+        }
+        Ok(self.bufs[buf].as_ref().map(|b| b[..n as usize].to_vec()))
+    }
+
+    // the buffer passed as argument k receives n bytes, None if it will hold
+    // bytes the model does not know. path strings are never written to
+    fn store(
+        &mut self,
+        prog: &Program,
+        s: &Syscall,
+        k: usize,
+        bytes: Option<Vec<u8>>,
+        n: i64,
+    ) -> Res<()> {
+        if n == 0 {
+            return Ok(());
+        }
+        let buf = self.buf_arg(s, k)?.ok_or(Expected::errno(EFAULT))?;
+        let is_str = matches!(*prog.variables[buf].var_type, VariableType::Str(_));
+        if n < 0 || n > self.caps[buf] || is_str {
+            return Err(Expected::UNKNOWN);
+        }
+        match (&mut self.bufs[buf], bytes) {
+            (Some(b), Some(x)) => b[..n as usize].copy_from_slice(&x[..n as usize]),
+            (b, _) => *b = None,
+        }
+        Ok(())
+    }
+
+    // argument k receives n bytes the model does not predict, returns 0
+    fn clobber(&mut self, prog: &Program, s: &Syscall, k: usize, n: i64) -> Res<i64> {
+        self.store(prog, s, k, None, n)?;
+        Ok(0)
+    }
+
+    fn read(&mut self, prog: &Program, s: &Syscall, at: Option<i64>) -> Res<i64> {
+        let f = self.io_file(s, false, at)?;
+        let count = self.val(s, 2)?;
+        if !(0..=MAX_RW_COUNT).contains(&count) {
+            return Err(Expected::UNKNOWN);
+        }
+        let ino = self.files[f].ino;
+        let offset = at.unwrap_or(self.files[f].offset);
+        let n = count.min(self.inodes[ino].size - offset).max(0);
+        let bytes = self.inodes[ino].read_at(offset, n);
+        self.store(prog, s, 1, bytes, n)?;
+        if at.is_none() {
+            self.files[f].offset += n;
+        }
+        Ok(n)
+    }
+
+    fn write(&mut self, s: &Syscall, at: Option<i64>) -> Res<i64> {
+        let f = self.io_file(s, true, at)?;
+        let count = self.val(s, 2)?;
+        if !(0..=MAX_RW_COUNT).contains(&count) {
+            return Err(Expected::UNKNOWN);
+        }
+        if count == 0 {
+            return Ok(0);
+        }
+        let bytes = self.load(s, 1, count)?;
+        let ino = self.files[f].ino;
+        // on Linux pwrite appends as well
+        let offset = match self.files[f].flags & O_APPEND != 0 {
+            true => self.inodes[ino].size,
+            false => at.unwrap_or(self.files[f].offset),
+        };
+        if offset.checked_add(count).is_none_or(|x| x > MAX_SAFE_SIZE) {
+            return Err(Expected::UNKNOWN);
+        }
+        self.inodes[ino].write_at(offset, bytes, count);
+        if at.is_none() {
+            self.files[f].offset = offset + count;
+        }
+        Ok(count)
+    }
+
+    fn lseek(&mut self, s: &Syscall) -> Res<i64> {
+        let f = self.file(s, 0)?;
+        let offset = self.val(s, 1)?;
+        let whence = self.val(s, 2)?;
+        let ino = self.files[f].ino;
+        let size = self.inodes[ino].size;
+        match self.inodes[ino].ftype {
+            FileType::Fifo | FileType::Socket => return err(ESPIPE),
+            FileType::Dir if whence == SEEK_SET && offset == 0 => {
+                self.files[f].listed = None;
+                return Ok(0);
+            }
+            FileType::Dir => return Err(Expected::UNKNOWN),
+            _ => {}
+        }
+        let new = match whence {
+            SEEK_SET => offset,
+            SEEK_CUR => self.files[f].offset.saturating_add(offset),
+            SEEK_END => size.saturating_add(offset),
+            // SEEK_DATA and SEEK_HOLE, where the holes are depends on the
+            // filesystem
+            3 | 4 if offset >= size => return err(ENXIO),
+            3 | 4 => return Err(Expected::UNKNOWN),
+            _ => return err(EINVAL),
+        };
+        if new < 0 {
+            return err(EINVAL);
+        }
+        if new > MAX_SAFE_SIZE {
+            return Err(Expected::UNKNOWN);
+        }
+        self.files[f].offset = new;
+        Ok(new)
+    }
+
+    fn getdents(&mut self, prog: &Program, s: &Syscall) -> Res<i64> {
+        let f = self.file(s, 0)?;
+        let count = self.val(s, 2)?;
+        let ino = self.files[f].ino;
+        let dir = &self.inodes[ino];
+        if dir.ftype != FileType::Dir {
+            return err(ENOTDIR);
+        }
+        if dir.nlink == 0 {
+            return err(ENOENT);
+        }
+        match self.files[f].listed {
+            Some(v) if v == dir.version => return Ok(0),
+            // entries added since may or may not show up
+            Some(_) => return Err(Expected::UNKNOWN),
+            None => {}
+        }
+        let reclen = |name: &str| (DIRENT_NAME + name.len() as i64 + 1 + 7) & !7;
+        if count < reclen(".") {
+            return err(EINVAL);
+        }
+        let total = reclen(".") + reclen("..") + dir.entries.keys().map(|x| reclen(x)).sum::<i64>();
+        // which entries fit depends on the order the filesystem keeps
+        if total > count {
+            return Err(Expected::UNKNOWN);
+        }
+        self.store(prog, s, 1, None, total)?;
+        self.files[f].listed = Some(self.inodes[ino].version);
+        Ok(total)
+    }
+
+    // move the entry lo to ln
+    fn rename(&mut self, lo: Lookup, ln: Lookup, flags: i64) -> Res<i64> {
+        let dots = |x: &str| x == "." || x == "..";
+        if dots(&lo.name) || dots(&ln.name) {
+            return Err(Expected::UNKNOWN);
+        }
+        let a = lo.ino.ok_or(Expected::errno(ENOENT))?;
+        let a_dir = self.inodes[a].ftype == FileType::Dir;
+        let exchange = flags & RENAME_EXCHANGE != 0;
+        if flags & RENAME_NOREPLACE != 0 && ln.ino.is_some() {
+            return err(EEXIST);
+        }
+        if exchange {
+            self.existing(&ln)?;
+        }
+        if !a_dir && (lo.dir_only || ln.dir_only) {
+            return err(ENOTDIR);
+        }
+        let b_dir = ln.ino.filter(|x| self.inodes[*x].ftype == FileType::Dir);
+        if lo.parent != ln.parent {
+            // moving a directory below itself
+            if a_dir && self.is_ancestor(a, ln.parent) {
+                return err(EINVAL);
+            }
+            if b_dir.is_some_and(|b| self.is_ancestor(b, lo.parent)) {
+                return err(if exchange { EINVAL } else { ENOTEMPTY });
+            }
+        }
+        // two names of the same inode, nothing happens
+        if ln.ino == Some(a) {
+            return Ok(0);
+        }
+        if exchange {
+            let b = ln.ino.ok_or(Expected::UNKNOWN)?;
+            self.remove_entry(lo.parent, &lo.name);
+            self.remove_entry(ln.parent, &ln.name);
+            self.add_entry(lo.parent, &lo.name, b);
+            self.add_entry(ln.parent, &ln.name, a);
+            return Ok(0);
+        }
+        if let Some(b) = ln.ino {
+            if a_dir && b_dir.is_none() {
+                return err(ENOTDIR);
+            }
+            if !a_dir && b_dir.is_some() {
+                return err(EISDIR);
+            }
+            if b_dir.is_some() && !self.inodes[b].entries.is_empty() {
+                return err(ENOTEMPTY);
+            }
+        }
+        self.remove_entry(ln.parent, &ln.name);
+        self.remove_entry(lo.parent, &lo.name);
+        self.add_entry(ln.parent, &ln.name, a);
+        Ok(0)
+    }
+
+    fn truncate(&mut self, ino: usize, length: i64) -> Res<i64> {
+        if length > MAX_SAFE_SIZE {
+            return Err(Expected::UNKNOWN);
+        }
+        self.inodes[ino].resize(length);
+        Ok(0)
+    }
+
+    fn mkdir(&mut self, dir: usize, path: &str, mode: i64) -> Res<i64> {
+        let l = self.lookup_entry(dir, path)?;
+        if l.ino.is_some() {
+            return err(EEXIST);
+        }
+        // mkdir only keeps the sticky bit of the set-id bits
+        self.create(&l, FileType::Dir, mode & 0o1777 & !self.umask);
+        Ok(0)
+    }
+
+    fn rmdir(&mut self, dir: usize, path: &str) -> Res<i64> {
+        let l = self.lookup_entry(dir, path)?;
+        match l.name.as_str() {
+            "." => return err(EINVAL),
+            ".." => return err(ENOTEMPTY),
+            _ => {}
+        }
+        let ino = self.existing(&l)?;
+        if self.inodes[ino].ftype != FileType::Dir {
+            return err(ENOTDIR);
+        }
+        if !self.inodes[ino].entries.is_empty() {
+            return err(ENOTEMPTY);
+        }
+        self.remove_entry(l.parent, &l.name);
+        Ok(0)
+    }
+
+    fn unlink(&mut self, dir: usize, path: &str) -> Res<i64> {
+        let l = self.lookup_entry(dir, path)?;
+        let ino = l.ino.ok_or(Expected::errno(ENOENT))?;
+        if self.inodes[ino].ftype == FileType::Dir {
+            return err(EISDIR);
+        }
+        if l.dir_only {
+            return err(ENOTDIR);
+        }
+        self.remove_entry(l.parent, &l.name);
+        Ok(0)
+    }
+
+    // new name ln for the inode ino
+    fn link(&mut self, ino: usize, ln: Lookup) -> Res<i64> {
+        if ln.ino.is_some() {
+            return err(EEXIST);
+        }
+        if ln.dir_only {
+            return Err(Expected::UNKNOWN);
+        }
+        if self.inodes[ino].ftype == FileType::Dir {
+            return err(EPERM);
+        }
+        self.add_entry(ln.parent, &ln.name, ino);
+        Ok(0)
+    }
+
+    fn symlink(&mut self, target: &str, dir: usize, path: &str) -> Res<i64> {
+        if target.is_empty() {
+            return err(ENOENT);
+        }
+        if target.len() >= PATH_MAX {
+            return err(ENAMETOOLONG);
+        }
+        let l = self.lookup_entry(dir, path)?;
+        if l.ino.is_some() {
+            return err(EEXIST);
+        }
+        if l.dir_only {
+            return Err(Expected::UNKNOWN);
+        }
+        let ino = self.create(&l, FileType::Symlink, 0o777);
+        self.inodes[ino].target = String::from(target);
+        self.inodes[ino].size = target.len() as i64;
+        self.inodes[ino].data = None;
+        Ok(0)
+    }
+
+    fn mknod(&mut self, dir: usize, path: &str, mode: i64) -> Res<i64> {
+        let ftype = match FileType::from_mode(mode) {
+            FileType::Dir => return err(EPERM),
+            FileType::Symlink | FileType::Unknown => return err(EINVAL),
+            ftype => ftype,
+        };
+        let l = self.lookup_entry(dir, path)?;
+        if l.ino.is_some() {
+            return err(EEXIST);
+        }
+        if l.dir_only {
+            return Err(Expected::UNKNOWN);
+        }
+        self.create(&l, ftype, mode & 0o7777 & !self.umask);
+        Ok(0)
+    }
+
+    fn utimensat(&mut self, prog: &Program, s: &Syscall) -> Res<i64> {
+        let nsecs = match self.buf_arg(s, 2)? {
+            Some(_) => {
+                let bytes = self.load(s, 2, 32)?.ok_or(Expected::UNKNOWN)?;
+                let nsec = |k: usize| {
+                    let mut x = [0u8; 8];
+                    x.copy_from_slice(&bytes[k * 16 + 8..k * 16 + 16]);
+                    i64::from_le_bytes(x)
+                };
+                vec![nsec(0), nsec(1)]
+            }
+            None => Vec::new(),
+        };
+        // omitting both times does not even look at the path
+        if !nsecs.is_empty() && nsecs.iter().all(|x| *x == UTIME_OMIT) {
+            return Ok(0);
+        }
+        let flags = self.val(s, 3)?;
+        if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+            return err(EINVAL);
+        }
+        let path = prog.arg_str(s.args.get(1)).ok_or(Expected::UNKNOWN)?;
+        self.at_target(s, 0, &path, flags)?;
+        let valid = |x: &i64| (0..1_000_000_000).contains(x) || *x == UTIME_NOW || *x == UTIME_OMIT;
+        if !nsecs.iter().all(valid) {
+            return err(EINVAL);
+        }
+        Ok(0)
+    }
+
+    // inode an xattr syscall works on
+    fn xattr_target(&self, prog: &Program, s: &Syscall) -> Res<usize> {
+        let follow = match s.nr {
+            SysNo::Fsetxattr | SysNo::Fgetxattr | SysNo::Fremovexattr | SysNo::Flistxattr => {
+                return Ok(self.files[self.file(s, 0)?].ino);
+            }
+            SysNo::Lsetxattr | SysNo::Lgetxattr | SysNo::Lremovexattr | SysNo::Llistxattr => false,
+            _ => true,
+        };
+        let path = prog.arg_str(s.args.first()).ok_or(Expected::UNKNOWN)?;
+        let l = self.lookup(ROOT, &path, follow)?;
+        self.existing(&l)
+    }
+
+    // checks every xattr syscall makes on the name before touching the inode
+    fn check_xattr_name(&self, ino: usize, name: &str, write: bool) -> Res<()> {
+        let known = ["user.", "trusted."];
+        let modeled = known.iter().find(|x| name.starts_with(*x));
+        let other = DEFAULT_XATTR_PREFIX.iter().any(|x| name.starts_with(x));
+        let ftype = self.inodes[ino].ftype;
+        // user xattrs only live on regular files and directories
+        if modeled == Some(&"user.") && ftype != FileType::File && ftype != FileType::Dir {
+            return err(if write { EPERM } else { ENODATA });
+        }
+        match modeled {
+            Some(prefix) if name.len() == prefix.len() => err(EINVAL),
+            Some(_) => Ok(()),
+            // system, security and filesystem namespaces depend on the
+            // filesystem and the security modules
+            None if other => Err(Expected::UNKNOWN),
+            None => err(EOPNOTSUPP),
+        }
+    }
+
+    fn set_xattr(
+        &mut self,
+        ino: usize,
+        name: &str,
+        value: Option<Vec<u8>>,
+        flags: i64,
+    ) -> Res<i64> {
+        self.check_xattr_name(ino, name, true)?;
+        let inode = &self.inodes[ino];
+        let value = value.ok_or(Expected::UNKNOWN)?;
+        if !inode.xattrs_known {
+            return Err(Expected::UNKNOWN);
+        }
+        let existing = inode.xattrs.iter().position(|x| x.0 == name);
+        if existing.is_some() && flags & XATTR_CREATE != 0 {
+            return err(EEXIST);
+        }
+        if existing.is_none() && flags & XATTR_REPLACE != 0 {
+            return err(ENODATA);
+        }
+        let used: usize = inode
+            .xattrs
+            .iter()
+            .filter(|x| x.0 != name)
+            .map(|x| x.0.len() + x.1.len())
+            .sum();
+        // how much fits is up to the filesystem
+        if used + name.len() + value.len() > self.xattr_space {
+            return Err(Expected::UNKNOWN);
+        }
+        let xattr = Xattr(String::from(name), value, flags);
+        let inode = &mut self.inodes[ino];
+        match existing {
+            Some(i) => inode.xattrs[i] = xattr,
+            None => inode.xattrs.push(xattr),
+        }
+        Ok(0)
+    }
+
+    fn get_xattr(&mut self, prog: &Program, s: &Syscall, ino: usize, name: &str) -> Res<i64> {
+        self.check_xattr_name(ino, name, false)?;
+        let size = self.val(s, 3)?;
+        let inode = &self.inodes[ino];
+        if !inode.xattrs_known {
+            return Err(Expected::UNKNOWN);
+        }
+        let value = match inode.xattrs.iter().find(|x| x.0 == name) {
+            Some(x) => x.1.clone(),
+            None => return err(ENODATA),
+        };
+        let len = value.len() as i64;
+        if size == 0 {
+            return Ok(len);
+        }
+        if size < len {
+            return err(ERANGE);
+        }
+        self.store(prog, s, 2, Some(value), len)?;
+        Ok(len)
+    }
+
+    fn remove_xattr(&mut self, ino: usize, name: &str) -> Res<i64> {
+        self.check_xattr_name(ino, name, true)?;
+        let inode = &mut self.inodes[ino];
+        if !inode.xattrs_known {
+            return Err(Expected::UNKNOWN);
+        }
+        match inode.xattrs.iter().position(|x| x.0 == name) {
+            Some(i) => {
+                inode.xattrs.remove(i);
+                Ok(0)
+            }
+            None => err(ENODATA),
+        }
+    }
+
+    fn list_xattr(&mut self, prog: &Program, s: &Syscall, ino: usize) -> Res<i64> {
+        let size = self.val(s, 2)?;
+        let inode = &self.inodes[ino];
+        if !inode.xattrs_known {
+            return Err(Expected::UNKNOWN);
+        }
+        let len: i64 = inode.xattrs.iter().map(|x| x.0.len() as i64 + 1).sum();
+        if size == 0 {
+            return Ok(len);
+        }
+        if size < len {
+            return err(ERANGE);
+        }
+        // the order of the names is up to the filesystem
+        self.store(prog, s, 1, None, len)?;
+        Ok(len)
+    }
+
+    fn sendfile(&mut self, s: &Syscall) -> Res<i64> {
+        if self.val(s, 2)? != 0 {
+            return Err(Expected::UNKNOWN);
+        }
+        let fi = self.file(s, 1)?;
+        let fo = self.file(s, 0)?;
+        if !self.files[fi].readable() || !self.files[fo].writable() {
+            return err(EBADF);
+        }
+        let (a, b) = (self.files[fi].ino, self.files[fo].ino);
+        match self.inodes[a].ftype {
+            FileType::File => {}
+            // a directory is only refused once there is something to copy
+            FileType::Dir if self.val(s, 3)? == 0 => return Err(Expected::UNKNOWN),
+            _ => return err(EINVAL),
+        }
+        if self.files[fo].flags & O_APPEND != 0 {
+            return err(EINVAL);
+        }
+        if self.inodes[b].ftype != FileType::File
+            || a == b
+            || (self.files[fi].flags | self.files[fo].flags) & O_DIRECT != 0
+        {
+            return Err(Expected::UNKNOWN);
+        }
+        let count = self.val(s, 3)?;
+        if count < 0 {
+            return Err(Expected::UNKNOWN);
+        }
+        let from = self.files[fi].offset;
+        let n = count
+            .min(MAX_RW_COUNT)
+            .min(self.inodes[a].size - from)
+            .max(0);
+        if n == 0 {
+            return Ok(0);
+        }
+        let to = self.files[fo].offset;
+        if to + n > MAX_SAFE_SIZE {
+            return Err(Expected::UNKNOWN);
+        }
+        let bytes = self.inodes[a].read_at(from, n);
+        self.inodes[b].write_at(to, bytes, n);
+        self.files[fi].offset += n;
+        self.files[fo].offset += n;
+        Ok(n)
+    }
+
+    fn copy_file_range(&mut self, s: &Syscall) -> Res<i64> {
+        let fi = self.file(s, 0)?;
+        let fo = self.file(s, 2)?;
+        if self.val(s, 1)? != 0 || self.val(s, 3)? != 0 {
+            return Err(Expected::UNKNOWN);
+        }
+        if self.val(s, 5)? != 0 {
+            return err(EINVAL);
+        }
+        let (a, b) = (self.files[fi].ino, self.files[fo].ino);
+        let types = [self.inodes[a].ftype, self.inodes[b].ftype];
+        if types.contains(&FileType::Dir) {
+            return err(EISDIR);
+        }
+        if types.iter().any(|x| *x != FileType::File) {
+            return err(EINVAL);
+        }
+        if !self.files[fi].readable()
+            || !self.files[fo].writable()
+            || self.files[fo].flags & O_APPEND != 0
+        {
+            return err(EBADF);
+        }
+        let len = self.val(s, 4)?;
+        // direct io through splice has alignment rules of its own
+        if len < 0 || (self.files[fi].flags | self.files[fo].flags) & O_DIRECT != 0 {
+            return Err(Expected::UNKNOWN);
+        }
+        let (from, to) = (self.files[fi].offset, self.files[fo].offset);
+        let n = len.min(MAX_RW_COUNT).min(self.inodes[a].size - from).max(0);
+        // the overlap only counts what lies within the source file
+        if a == b && from < to + n && to < from + n {
+            return err(EINVAL);
+        }
+        if n == 0 {
+            return Ok(0);
+        }
+        if to + n > MAX_SAFE_SIZE {
+            return Err(Expected::UNKNOWN);
+        }
+        let bytes = self.inodes[a].read_at(from, n);
+        self.inodes[b].write_at(to, bytes, n);
+        self.files[fi].offset += n;
+        self.files[fo].offset += n;
+        Ok(n)
+    }
+
+    fn fallocate(&mut self, s: &Syscall) -> Res<i64> {
+        let f = self.file(s, 0)?;
+        let mode = self.val(s, 1)?;
+        let offset = self.val(s, 2)?;
+        let len = self.val(s, 3)?;
+        if offset < 0 || len <= 0 {
+            return err(EINVAL);
+        }
+        let known = FALLOC_FL_KEEP_SIZE
+            | FALLOC_FL_PUNCH_HOLE
+            | FALLOC_FL_NO_HIDE_STALE
+            | FALLOC_FL_COLLAPSE_RANGE
+            | FALLOC_FL_ZERO_RANGE
+            | FALLOC_FL_INSERT_RANGE
+            | FALLOC_FL_UNSHARE_RANGE
+            | FALLOC_FL_WRITE_ZEROES;
+        if mode & !known != 0 {
+            return err(EOPNOTSUPP);
+        }
+        match mode & !FALLOC_FL_KEEP_SIZE {
+            0 => {}
+            FALLOC_FL_PUNCH_HOLE if mode & FALLOC_FL_KEEP_SIZE == 0 => return err(EOPNOTSUPP),
+            FALLOC_FL_PUNCH_HOLE => {}
+            // which other modes and combinations are refused, and how,
+            // changed between kernels and depends on the filesystem
+            _ => return Err(Expected::UNKNOWN),
+        }
+        if !self.files[f].writable() {
+            return err(EBADF);
+        }
+        let ino = self.files[f].ino;
+        match self.inodes[ino].ftype {
+            FileType::File => {}
+            FileType::Dir => return err(EISDIR),
+            FileType::Fifo => return err(ESPIPE),
+            _ => return err(ENODEV),
+        }
+        let end = offset.checked_add(len).ok_or(Expected::errno(EFBIG))?;
+        if end > MAX_SAFE_SIZE {
+            return Err(Expected::UNKNOWN);
+        }
+        if mode & FALLOC_FL_PUNCH_HOLE != 0 {
+            self.inodes[ino].zero(offset, end);
+        }
+        if mode & FALLOC_FL_KEEP_SIZE == 0 && end > self.inodes[ino].size {
+            self.inodes[ino].resize(end);
+        }
+        Ok(0)
+    }
+
+    // the mapping variable passed as argument k, None if it holds MAP_FAILED
+    fn map_arg(&self, s: &Syscall, k: usize) -> Res<Option<usize>> {
+        let index = arg_var(s.args.get(k)).ok_or(Expected::UNKNOWN)? as usize;
+        if self.maps.contains_key(&index) {
+            return Ok(Some(index));
+        }
+        match self.values.get(index).copied().flatten() {
+            Some(-1) => Ok(None),
+            _ => Err(Expected::UNKNOWN),
+        }
+    }
+
+    fn mmap(&mut self, s: &Syscall) -> Res<i64> {
+        let len = self.val(s, 1)?;
+        let prot = self.val(s, 2)?;
+        let flags = self.val(s, 3)?;
+        let offset = self.val(s, 5)?;
+        if self.val(s, 0)? != 0 || flags & MAP_FIXED != 0 || len < 0 || offset < 0 {
+            return Err(Expected::UNKNOWN);
+        }
+        if offset & (Program::PAGE_SIZE as i64 - 1) != 0 {
+            return err(EINVAL);
+        }
+        let f = match flags & MAP_ANONYMOUS != 0 {
+            // depends on the huge pages the system has reserved
+            true if flags & MAP_HUGETLB != 0 => return Err(Expected::UNKNOWN),
+            true => None,
+            // only files on hugetlbfs can be mapped with huge pages
+            false if flags & MAP_HUGETLB != 0 => {
+                self.file(s, 4)?;
+                return err(EINVAL);
+            }
+            false => Some(self.file(s, 4)?),
+        };
+        if len == 0 {
+            return err(EINVAL);
+        }
+        // no room for it below the top of the user address space
+        let len = match page_round(len) {
+            Some(n) if n <= MMAP_LIMIT => n,
+            _ => return err(ENOMEM),
+        };
+        // the mapped range must end below the largest file offset
+        if f.is_some() && offset.checked_add(len).is_none() {
+            return err(EOVERFLOW);
+        }
+        // the low four bits are the mapping type, MAP_SHARED_VALIDATE also
+        // checks the other flags against what the kernel knows
+        match flags & 0xf {
+            MAP_SHARED | MAP_PRIVATE => {}
+            MAP_SHARED_VALIDATE => return Err(Expected::UNKNOWN),
+            _ => return err(EINVAL),
+        }
+        let shared = flags & MAP_SHARED != 0;
+        let writable = prot & PROT_WRITE != 0;
+        let ino = match f {
+            None => None,
+            Some(f) => {
+                let file = &self.files[f];
+                // the access mode is checked before the file type
+                if !file.readable()
+                    || shared && writable && (!file.writable() || file.flags & O_APPEND != 0)
+                {
+                    return err(EACCES);
+                }
+                match self.inodes[file.ino].ftype {
+                    FileType::File => {}
+                    _ => return err(ENODEV),
+                }
+                Some(file.ino)
+            }
+        };
+        if s.ret_index != -1 {
+            let map = Mapping {
+                ino,
+                offset,
+                len,
+                hole: 0,
+                shared,
+                writable,
+            };
+            self.maps.insert(s.ret_index as usize, map);
+        }
+        Err(Expected::SUCCESS)
+    }
+
+    // msync and madvise: the range from the mapping start must be mapped
+    fn map_range(&self, s: &Syscall) -> Res<i64> {
+        let m = self.map_arg(s, 0)?.ok_or(Expected::errno(EINVAL))?;
+        let len = self.val(s, 1)?;
+        if len < 0 {
+            return err(EINVAL);
+        }
+        if len == 0 {
+            return Ok(0);
+        }
+        let map = &self.maps[&m];
+        if map.hole > 0 {
+            return Err(Expected::errno(ENOMEM));
+        }
+        // whatever lies past the mapping is not known
+        if page_round(len).is_none_or(|n| n > map.len) {
+            return Err(Expected::UNKNOWN);
+        }
+        Ok(0)
+    }
+
+    fn madvise(&mut self, s: &Syscall) -> Res<i64> {
+        let advice = self.val(s, 2)?;
+        self.map_range(s)?;
+        let len = self.val(s, 1)?;
+        match advice {
+            MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED | MADV_COLD
+            | MADV_PAGEOUT | MADV_DONTNEED => Ok(0),
+            MADV_REMOVE if len == 0 => Ok(0),
+            MADV_REMOVE => {
+                let m = self.map_arg(s, 0)?.ok_or(Expected::errno(EINVAL))?;
+                let map = self.maps[&m].clone();
+                match map.ino {
+                    Some(ino) if map.shared && map.writable => {
+                        let len = page_round(len).ok_or(Expected::UNKNOWN)?;
+                        self.inodes[ino].zero(map.offset, map.offset + len);
+                        Ok(0)
+                    }
+                    _ => Err(Expected::UNKNOWN),
+                }
+            }
+            _ => Err(Expected::UNKNOWN),
+        }
+    }
+
+    fn mremap(&mut self, s: &Syscall) -> Res<i64> {
+        let old_size = self.val(s, 1)?;
+        let new_size = self.val(s, 2)?;
+        let flags = self.val(s, 3)?;
+        if flags & !(MREMAP_MAYMOVE | MREMAP_FIXED | 4) != 0 {
+            return err(EINVAL);
+        }
+        let m = self.map_arg(s, 0)?.ok_or(Expected::errno(EINVAL))?;
+        if new_size == 0 {
+            return err(EINVAL);
+        }
+        let map = self.maps[&m].clone();
+        let (old_len, new_len) = match (page_round(old_size), page_round(new_size)) {
+            (Some(o), Some(n)) if new_size > 0 => (o, n),
+            _ => return Err(Expected::UNKNOWN),
+        };
+        if flags != MREMAP_MAYMOVE && flags != 0
+            || old_size <= 0
+            || map.hole > 0
+            || old_len > map.len
+        {
+            return Err(Expected::UNKNOWN);
+        }
+        // growing in place depends on what lies after the mapping
+        if new_len > old_len && flags & MREMAP_MAYMOVE == 0 {
+            return Err(Expected::UNKNOWN);
+        }
+        if let Some(old) = self.maps.get_mut(&m) {
+            old.hole = old.len;
+        }
+        if s.ret_index != -1 {
+            let new = Mapping {
+                len: new_len,
+                ..map
+            };
+            self.maps.insert(s.ret_index as usize, new);
+        }
+        Err(Expected::SUCCESS)
+    }
+
+    // the mapping the memcpy_to_map s copies to, None for MAP_FAILED where
+    // the copy is skipped. a copy that may fault is unknown
+    fn copy_target(&self, s: &Syscall) -> Res<Option<&Mapping>> {
+        let offset = self.val(s, 1)?;
+        let len = self.val(s, 3)?;
+        let map = match self.map_arg(s, 0)? {
+            Some(m) => &self.maps[&m],
+            None => return Ok(None),
+        };
+        if offset < map.hole
+            || offset.checked_add(len).is_none_or(|end| end > map.len)
+            || !map.writable
+        {
+            return Err(Expected::UNKNOWN);
+        }
+        if let Some(ino) = map.ino {
+            // SIGBUS past the page holding the end of the file, private
+            // mappings included
+            let end = page_round(self.inodes[ino].size);
+            if end.is_none_or(|end| map.offset + offset + len > end) {
+                return Err(Expected::UNKNOWN);
+            }
+        }
+        Ok(Some(map))
+    }
+
+    // whether the memcpy_to_map s, placed after the syscalls stepped
+    // through so far, copies without faulting
+    pub fn copy_succeeds(&self, s: &Syscall) -> bool {
+        !self.diverged && self.copy_target(s).is_ok()
+    }
+
+    fn memcpy_to_map(&mut self, s: &Syscall) -> Res<i64> {
+        let map = match self.copy_target(s)? {
+            Some(map) => map.clone(),
+            None => return Err(Expected::SUCCESS),
+        };
+        let offset = self.val(s, 1)?;
+        let len = self.val(s, 3)?;
+        let bytes = self.load(s, 2, len)?;
+        if let Some(ino) = map.ino {
+            let at = map.offset + offset;
+            // what lands past the end of the file is never written back
+            let n = len.min(self.inodes[ino].size - at).max(0);
+            if map.shared && n > 0 {
+                self.inodes[ino].write_at(at, bytes, n);
+            }
+        }
+        Err(Expected::SUCCESS)
+    }
+}
+
+// syscalls that only report, an unknown result leaves the model intact
+fn is_query(nr: SysNo) -> bool {
+    matches!(
+        nr,
+        SysNo::Fstat
+            | SysNo::Stat
+            | SysNo::Lstat
+            | SysNo::Fstatat
+            | SysNo::Access
+            | SysNo::Getxattr
+            | SysNo::Lgetxattr
+            | SysNo::Fgetxattr
+            | SysNo::Listxattr
+            | SysNo::Llistxattr
+            | SysNo::Flistxattr
+            | SysNo::Fsync
+            | SysNo::Fdatasync
+            | SysNo::Syncfs
+            | SysNo::SyncFileRange
+            | SysNo::Fadvise64
+            | SysNo::Readahead
+            | SysNo::Msync
+    )
+}
+
+// open flags the kernel refuses before it looks at the path
+fn check_open_flags(flags: i64) -> Res<()> {
+    // O_PATH drops every other flag but a few
+    if flags & O_PATH != 0 || flags & 3 == 3 {
+        return Err(Expected::UNKNOWN);
+    }
+    // O_TMPFILE without the O_DIRECTORY bit
+    let tmpfile = O_TMPFILE & !O_DIRECTORY;
+    if flags & tmpfile != 0 {
+        if flags & (O_TMPFILE | O_CREAT) != O_TMPFILE || flags & 3 == O_RDONLY {
+            return err(EINVAL);
+        }
+        return Err(Expected::UNKNOWN);
+    }
+    if flags & O_CREAT != 0 && flags & O_DIRECTORY != 0 {
+        return err(EINVAL);
+    }
+    Ok(())
+}
+
+fn check_xattr_len(name: &str) -> Res<()> {
+    if name.is_empty() || name.len() > XATTR_NAME_MAX {
+        return err(ERANGE);
+    }
+    Ok(())
+}
+
+// n rounded up to whole pages, None if that does not fit
+pub(crate) fn page_round(n: i64) -> Option<i64> {
+    let page = Program::PAGE_SIZE as i64;
+    Some(n.checked_add(page - 1)? / page * page)
+}
+
+impl Program {
+    // expected result of each syscall under a fresh FsModel. fd variables
+    // get the type of what they were opened on, and unless the model lost
+    // track, the file objects are rebuilt from its final tree
+    pub fn simulate(&mut self) -> Result<Vec<Expected>, HdError> {
+        let mut model = FsModel::new();
+        model.start(self);
+        let mut expected = Vec::<Expected>::with_capacity(self.syscalls.len());
+        let mut opened = Vec::<(usize, FileType)>::new();
+        for s in self.syscalls.iter() {
+            let e = model.step(self, s);
+            if let (Ok(ret), Some(ftype)) = (
+                usize::try_from(s.ret_index),
+                e.ret.and_then(|fd| model.fd_type(fd)),
+            ) {
+                opened.push((ret, ftype));
+            }
+            expected.push(e);
+        }
+        // an fd variable takes the type of the file it ended up open on,
+        // unless a use needs the old one. that syscall then just fails
+        for (ret, ftype) in opened {
+            if !self.variables[ret].kind.is_fd_kind() {
+                continue;
+            }
+            let old = std::mem::replace(&mut self.variables[ret].kind, ftype);
+            if !self.uses_fit(ret as i64) {
+                self.variables[ret].kind = old;
+            }
+        }
+        if !model.diverged {
+            self.sync_files(&model)?;
+        }
+        Ok(expected)
+    }
+
+    // whether every argument passing variable index fits its signature
+    fn uses_fit(&self, index: i64) -> bool {
+        self.syscalls.iter().all(|s| {
+            let sig = signature(s.nr);
+            s.args
+                .iter()
+                .zip(sig.iter())
+                .filter(|(arg, _)| arg_var(Some(arg)) == Some(index))
+                .all(|(arg, kind)| arg_matches(self, *kind, arg))
+        })
+    }
+
+    // replace the file objects with the files of the model, keeping the
+    // times and anything else the model does not know from the old ones
+    pub fn sync_files(&mut self, model: &FsModel) -> Result<(), HdError> {
+        let old = std::mem::take(&mut self.avail_files);
+        self.avail_dirs.clear();
+        self.avail_non_dirs.clear();
+        for (path, ino) in model.paths() {
+            let inode = &model.inodes[ino];
+            let prev = old.iter().find(|x| x.rel_path == path);
+            let index = self.path_index(&path);
+            let mut fobj = FileObject::new(&path, inode.ftype, index);
+            if let Some(prev) = prev {
+                fobj.meta = prev.meta.clone();
+                fobj.xattrs = prev.xattrs.clone();
+                fobj.initial_xattrs = prev.initial_xattrs.clone();
+            }
+            if inode.xattrs_known {
+                fobj.xattrs = inode.xattrs.clone();
+            }
+            if inode.ftype != FileType::Symlink {
+                fobj.meta.mode = Some(inode.mode);
+            }
+            if inode.uid.is_some() {
+                fobj.meta.uid = inode.uid;
+            }
+            if inode.gid.is_some() {
+                fobj.meta.gid = inode.gid;
+            }
+            self.try_add_file(fobj, index)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(prog: &Program) -> Vec<Expected> {
+        FsModel::new().run(prog)
+    }
+
+    #[test]
+    fn open_creates_and_reports_missing_files() {
+        let mut prog = Program::new();
+        prog.open("a", O_RDONLY, 0).unwrap();
+        prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.open("a", O_CREAT | O_EXCL | O_RDWR, 0o644).unwrap();
+        prog.open("a", O_RDONLY, 0).unwrap();
+        prog.open("a/b", O_RDONLY, 0).unwrap();
+        assert_eq!(
+            run(&prog),
+            vec![
+                Expected::errno(ENOENT),
+                Expected::ret(3),
+                Expected::errno(EEXIST),
+                Expected::ret(4),
+                Expected::errno(ENOTDIR),
+            ]
+        );
+    }
+
+    #[test]
+    fn rename_moves_the_name() {
+        let mut prog = Program::new();
+        prog.rename("a", "b").unwrap();
+        let fd = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.close(fd).unwrap();
+        prog.rename("a", "b").unwrap();
+        prog.open("a", O_RDONLY, 0).unwrap();
+        prog.open("b", O_RDONLY, 0).unwrap();
+        prog.mkdir("d", 0o755).unwrap();
+        prog.rename("b", "d").unwrap();
+        assert_eq!(
+            run(&prog),
+            vec![
+                Expected::errno(ENOENT),
+                Expected::ret(3),
+                Expected::ret(0),
+                Expected::ret(0),
+                Expected::errno(ENOENT),
+                Expected::ret(3),
+                Expected::ret(0),
+                Expected::errno(EISDIR),
+            ]
+        );
+    }
+
+    #[test]
+    fn unlink_removes_files_only() {
+        let mut prog = Program::new();
+        let fd = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.unlink("a").unwrap();
+        prog.unlink("a").unwrap();
+        prog.mkdir("d", 0o755).unwrap();
+        prog.unlink("d").unwrap();
+        // the open file outlives its name
+        prog.close(fd).unwrap();
+        assert_eq!(
+            run(&prog),
+            vec![
+                Expected::ret(3),
+                Expected::ret(0),
+                Expected::errno(ENOENT),
+                Expected::ret(0),
+                Expected::errno(EISDIR),
+                Expected::ret(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn xattrs_follow_their_flags() {
+        let mut prog = Program::new();
+        let buf = prog.create_variable(VariableType::UCharPtr(None, 64));
+        let fd = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.close(fd).unwrap();
+        prog.getxattr("a", "user.x", buf, 64).unwrap();
+        prog.setxattr("a", "user.x", b"value", XATTR_REPLACE)
+            .unwrap();
+        prog.setxattr("a", "user.x", b"value", XATTR_CREATE)
+            .unwrap();
+        prog.setxattr("a", "user.x", b"value", XATTR_CREATE)
+            .unwrap();
+        prog.getxattr("a", "user.x", buf, 64).unwrap();
+        prog.getxattr("a", "user.x", buf, 2).unwrap();
+        prog.removexattr("a", "user.x").unwrap();
+        prog.removexattr("a", "user.x").unwrap();
+        assert_eq!(
+            run(&prog)[2..],
+            [
+                Expected::errno(ENODATA),
+                Expected::errno(ENODATA),
+                Expected::ret(0),
+                Expected::errno(EEXIST),
+                Expected::ret(5),
+                Expected::errno(ERANGE),
+                Expected::ret(0),
+                Expected::errno(ENODATA),
+            ]
+        );
+    }
+
+    #[test]
+    fn huge_mapping_lengths_do_not_overflow() {
+        let mut prog = Program::new();
+        let fd = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        let base = prog.mmap(fd, 4096, PROT_READ, MAP_SHARED, 0).unwrap();
+        prog.munmap(base, i64::MAX).unwrap();
+        prog.mmap(fd, i64::MAX, PROT_READ, MAP_SHARED, 0).unwrap();
+        prog.mmap(fd, 4096, PROT_READ, MAP_SHARED | MAP_HUGETLB, 0)
+            .unwrap();
+        assert_eq!(
+            run(&prog)[1..],
+            [
+                Expected::SUCCESS,
+                Expected::errno(EINVAL),
+                Expected::errno(ENOMEM),
+                Expected::errno(EINVAL),
+            ]
+        );
+    }
+}
@@ -0,0 +1,836 @@
+use crate::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+// sizes and offsets around page and buffer boundaries
+pub const INTERESTING_LENGTHS: &[i64] = &[0, 1, 511, 512, 4095, 4096, 4097, 8191, 8192];
+pub const INTERESTING_OFFSETS: &[i64] = &[0, 1, 4095, 4096, 4097, 8192, 1 << 20, 1 << 32];
+
+const OPEN_FLAGS: &[i64] = &[
+    O_RDONLY,
+    O_WRONLY,
+    O_RDWR,
+    O_RDWR | O_CREAT,
+    O_RDWR | O_CREAT | O_EXCL,
+    O_RDWR | O_CREAT | O_TRUNC,
+    O_WRONLY | O_CREAT | O_APPEND,
+    O_RDWR | O_SYNC,
+    O_RDWR | O_DIRECT,
+    O_RDONLY | O_NOFOLLOW | O_CLOEXEC,
+];
+const MODES: &[i64] = &[0o644, 0o755, 0o600, 0o777, 0o000, 0o4755, 0o2755, 0o1777];
+const NODE_TYPES: &[i64] = &[S_IFREG, S_IFIFO, S_IFSOCK, S_IFCHR, S_IFBLK];
+const IDS: &[i64] = &[0, 1, 1000, 65534, -1];
+
+// how often nr is picked unless configured otherwise
+pub fn default_weight(nr: SysNo) -> u32 {
+    match nr {
+        SysNo::Open | SysNo::Openat | SysNo::Write | SysNo::Pwrite => 8,
+        SysNo::Read | SysNo::Pread | SysNo::Mkdir | SysNo::Mkdirat => 4,
+        SysNo::Close | SysNo::Rename | SysNo::Fsync | SysNo::Setxattr => 3,
+        // the crash harness places its own
+        SysNo::CrashPoint => 0,
+        _ => 1,
+    }
+}
+
+// builds random programs out of the Program builder methods, so every
+// program it returns passes Program::validate
+pub struct ProgramGenerator {
+    rng: StdRng,
+    weights: HashMap<SysNo, u32>,
+}
+
+impl ProgramGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            weights: SysNo::ALL
+                .iter()
+                .map(|nr| (*nr, default_weight(*nr)))
+                .collect(),
+        }
+    }
+
+    // 0 never picks nr
+    pub fn set_weight(&mut self, nr: SysNo, weight: u32) -> &mut Self {
+        self.weights.insert(nr, weight);
+        self
+    }
+
+    pub fn weight(&self, nr: SysNo) -> u32 {
+        self.weights.get(&nr).copied().unwrap_or(0)
+    }
+
+    // a fresh program of len syscalls
+    pub fn generate(&mut self, len: usize) -> Program {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        self.extend(&mut prog, len);
+        prog
+    }
+
+    // append up to len syscalls, stops early if no syscall can be picked
+    pub fn extend(&mut self, prog: &mut Program, len: usize) {
+        // follows prog a syscall at a time instead of replaying it per check
+        let mut model = FsModel::new();
+        model.run(prog);
+        for _ in 0..len {
+            let choices: Vec<(SysNo, u32)> = SysNo::ALL
+                .iter()
+                .map(|nr| (*nr, self.weight(*nr)))
+                .filter(|(nr, w)| *w > 0 && applicable(prog, *nr))
+                .collect();
+            let dist = match WeightedIndex::new(choices.iter().map(|x| x.1)) {
+                Ok(d) => d,
+                Err(_) => return,
+            };
+            let nr = choices[dist.sample(&mut self.rng)].0;
+            // a builder refusing its arguments leaves the program as it was
+            let saved = Checkpoint::new(prog);
+            let stepped = prog.syscalls.len();
+            if self.add_after(prog, &model, nr).is_err() {
+                saved.restore(prog);
+                continue;
+            }
+            model.add_variables(prog);
+            for s in prog.syscalls[stepped..].iter() {
+                model.step(prog, s);
+            }
+        }
+    }
+
+    // append one syscall of kind nr with random arguments
+    pub fn add(&mut self, prog: &mut Program, nr: SysNo) -> Result<(), HdError> {
+        let mut model = FsModel::new();
+        model.run(prog);
+        self.add_after(prog, &model, nr)
+    }
+
+    // add with model stepped through every syscall of prog
+    fn add_after(&mut self, prog: &mut Program, model: &FsModel, nr: SysNo) -> Result<(), HdError> {
+        let (src, dest) = buffers(prog);
+        match nr {
+            SysNo::Open => {
+                let path = self.any_path(prog);
+                let flags = self.open_flags(prog, &path);
+                let mode = self.choose(MODES);
+                prog.open(&path, flags, mode).map(|_| ())
+            }
+            SysNo::Openat => {
+                let (dirfd, path) = self.at_path(prog);
+                let full = prog.resolve_at(dirfd, &path).unwrap_or_default();
+                let flags = self.open_flags(prog, &full);
+                let mode = self.choose(MODES);
+                prog.openat(dirfd, &path, flags, mode).map(|_| ())
+            }
+            SysNo::Read | SysNo::Write => {
+                let fd = self.choose(&prog.active_file_fds);
+                let count = self.count();
+                match nr {
+                    SysNo::Read => prog.read(fd, dest, count),
+                    _ => prog.write(fd, src, count),
+                }
+            }
+            SysNo::Pread | SysNo::Pwrite => {
+                let fd = self.choose(&prog.active_file_fds);
+                let count = self.count();
+                let offset = self.choose(INTERESTING_OFFSETS);
+                match nr {
+                    SysNo::Pread => prog.pread(fd, dest, count, offset),
+                    _ => prog.pwrite(fd, src, count, offset),
+                }
+            }
+            SysNo::Lseek => {
+                let fd = self.choose(&prog.active_fds);
+                let offset = self.choose(INTERESTING_OFFSETS);
+                let whence = self.choose(&[SEEK_SET, SEEK_CUR, SEEK_END]);
+                prog.lseek(fd, offset, whence)
+            }
+            SysNo::Getdents => {
+                let fd = self.choose(&prog.active_dir_fds);
+                prog.getdents(fd, dest, Program::PAGE_SIZE as i64 * 2)
+            }
+            SysNo::Fstat => {
+                let fd = self.choose(&prog.active_fds);
+                prog.fstat(fd, dest)
+            }
+            SysNo::Stat | SysNo::Lstat => {
+                let path = self.any_path(prog);
+                match nr {
+                    SysNo::Stat => prog.stat(&path, dest),
+                    _ => prog.lstat(&path, dest),
+                }
+            }
+            SysNo::Rename => {
+                let old = self.any_path(prog);
+                let new = self.any_path(prog);
+                prog.rename(&old, &new)
+            }
+            SysNo::Fsync | SysNo::Fdatasync | SysNo::Syncfs => {
+                let fd = self.choose(&prog.active_fds);
+                match nr {
+                    SysNo::Fsync => prog.fsync(fd),
+                    SysNo::Fdatasync => prog.fdatasync(fd),
+                    _ => prog.syncfs(fd),
+                }
+            }
+            SysNo::Sendfile => {
+                let out_fd = self.choose(&prog.active_file_fds);
+                let in_fd = self.choose(&prog.active_file_fds);
+                let count = self.length();
+                prog.sendfile(out_fd, in_fd, count)
+            }
+            SysNo::Access => {
+                let path = self.any_path(prog);
+                let mode = self.rng.gen_range(0..8);
+                prog.access(&path, mode)
+            }
+            SysNo::Ftruncate => {
+                let fd = self.choose(&prog.active_file_fds);
+                let length = self.length();
+                prog.ftruncate(fd, length)
+            }
+            SysNo::Truncate => {
+                let path = self.non_dir_path(prog);
+                let length = self.length();
+                prog.truncate(&path, length)
+            }
+            SysNo::Mkdir => {
+                let path = self.fresh_path(prog, "d");
+                let mode = self.choose(MODES);
+                prog.mkdir(&path, mode)
+            }
+            SysNo::Rmdir => {
+                let path = self.dir_path(prog);
+                prog.rmdir(&path)
+            }
+            SysNo::Link => {
+                let old = self.non_dir_path(prog);
+                let new = self.fresh_path(prog, "h");
+                prog.link(&old, &new)
+            }
+            SysNo::Unlink => {
+                let path = self.non_dir_path(prog);
+                prog.unlink(&path)
+            }
+            SysNo::Symlink => {
+                let target = self.any_path(prog);
+                let linkpath = self.fresh_path(prog, "s");
+                prog.symlink(&target, &linkpath)
+            }
+            SysNo::Setxattr | SysNo::Lsetxattr | SysNo::Fsetxattr => {
+                let x = gen_xattr(&mut self.rng);
+                match nr {
+                    SysNo::Setxattr => {
+                        let path = self.any_path(prog);
+                        prog.setxattr(&path, &x.0, &x.1, x.2)
+                    }
+                    SysNo::Lsetxattr => {
+                        let path = self.link_path(prog);
+                        prog.lsetxattr(&path, &x.0, &x.1, x.2)
+                    }
+                    _ => {
+                        let fd = self.choose(&prog.active_fds);
+                        prog.fsetxattr(fd, &x.0, &x.1, x.2)
+                    }
+                }
+            }
+            SysNo::Getxattr | SysNo::Lgetxattr | SysNo::Fgetxattr => {
+                let size = self.count();
+                match nr {
+                    SysNo::Getxattr => {
+                        let path = self.any_path(prog);
+                        let name = self.xattr_name(prog, &path);
+                        prog.getxattr(&path, &name, dest, size)
+                    }
+                    SysNo::Lgetxattr => {
+                        let path = self.link_path(prog);
+                        let name = self.xattr_name(prog, &path);
+                        prog.lgetxattr(&path, &name, dest, size)
+                    }
+                    _ => {
+                        let fd = self.choose(&prog.active_fds);
+                        let path = prog.fd_path(fd).unwrap_or_default();
+                        let name = self.xattr_name(prog, &path);
+                        prog.fgetxattr(fd, &name, dest, size)
+                    }
+                }
+            }
+            SysNo::Removexattr | SysNo::Lremovexattr | SysNo::Fremovexattr => match nr {
+                SysNo::Removexattr => {
+                    let path = self.any_path(prog);
+                    let name = self.xattr_name(prog, &path);
+                    prog.removexattr(&path, &name)
+                }
+                SysNo::Lremovexattr => {
+                    let path = self.link_path(prog);
+                    let name = self.xattr_name(prog, &path);
+                    prog.lremovexattr(&path, &name)
+                }
+                _ => {
+                    let fd = self.choose(&prog.active_fds);
+                    let path = prog.fd_path(fd).unwrap_or_default();
+                    let name = self.xattr_name(prog, &path);
+                    prog.fremovexattr(fd, &name)
+                }
+            },
+            SysNo::Listxattr | SysNo::Llistxattr | SysNo::Flistxattr => {
+                let size = self.count();
+                match nr {
+                    SysNo::Listxattr => {
+                        let path = self.any_path(prog);
+                        prog.listxattr(&path, dest, size)
+                    }
+                    SysNo::Llistxattr => {
+                        let path = self.link_path(prog);
+                        prog.llistxattr(&path, dest, size)
+                    }
+                    _ => {
+                        let fd = self.choose(&prog.active_fds);
+                        prog.flistxattr(fd, dest, size)
+                    }
+                }
+            }
+            SysNo::Mkdirat => {
+                let dirfd = self.choose(&prog.dirfd_choices());
+                let path = self.fresh_name(prog, dirfd, "d");
+                let mode = self.choose(MODES);
+                prog.mkdirat(dirfd, &path, mode)
+            }
+            SysNo::Unlinkat => {
+                let (dirfd, path) = self.at_path(prog);
+                let full = prog.resolve_at(dirfd, &path);
+                let flags = match full.and_then(|x| prog.find_file(&x)).map(|x| x.ftype) {
+                    Some(FileType::Dir) => AT_REMOVEDIR,
+                    _ => 0,
+                };
+                prog.unlinkat(dirfd, &path, flags)
+            }
+            SysNo::Renameat2 => {
+                let (olddirfd, old) = self.at_path(prog);
+                let (newdirfd, new) = self.at_path(prog);
+                let flags = self.choose(&[0, RENAME_NOREPLACE, RENAME_EXCHANGE]);
+                prog.renameat2(olddirfd, &old, newdirfd, &new, flags)
+            }
+            SysNo::Linkat => {
+                let (olddirfd, old) = self.at_path(prog);
+                let newdirfd = self.choose(&prog.dirfd_choices());
+                let new = self.fresh_name(prog, newdirfd, "h");
+                let flags = self.choose(&[0, AT_SYMLINK_FOLLOW]);
+                prog.linkat(olddirfd, &old, newdirfd, &new, flags)
+            }
+            SysNo::Symlinkat => {
+                let target = self.any_path(prog);
+                let newdirfd = self.choose(&prog.dirfd_choices());
+                let linkpath = self.fresh_name(prog, newdirfd, "s");
+                prog.symlinkat(&target, newdirfd, &linkpath)
+            }
+            SysNo::Fstatat => {
+                let (dirfd, path) = self.at_path(prog);
+                let flags = self.choose(&[0, AT_SYMLINK_NOFOLLOW]);
+                prog.fstatat(dirfd, &path, dest, flags)
+            }
+            SysNo::Mmap => {
+                let fd = self.choose(&prog.active_file_fds);
+                let length = Program::PAGE_SIZE as i64 * self.rng.gen_range(1..4);
+                let prot = self.choose(&[PROT_READ, PROT_READ | PROT_WRITE]);
+                let flags = self.choose(&[MAP_SHARED, MAP_PRIVATE, MAP_SHARED | MAP_POPULATE]);
+                let offset = self.choose(&[0, Program::PAGE_SIZE as i64]);
+                prog.mmap(fd, length, prot, flags, offset).map(|_| ())
+            }
+            SysNo::Munmap | SysNo::Msync | SysNo::Madvise | SysNo::Mremap => {
+                let base = self.choose(&prog.active_map_base_idx);
+                let size = prog.map_size(base).unwrap_or(0);
+                match nr {
+                    SysNo::Munmap => prog.munmap(base, size),
+                    SysNo::Msync => {
+                        let flags = self.choose(&[MS_ASYNC, MS_SYNC, MS_SYNC | MS_INVALIDATE]);
+                        prog.msync(base, size, flags)
+                    }
+                    SysNo::Madvise => {
+                        let advice = self.choose(&[
+                            MADV_NORMAL,
+                            MADV_RANDOM,
+                            MADV_SEQUENTIAL,
+                            MADV_WILLNEED,
+                            MADV_DONTNEED,
+                            MADV_REMOVE,
+                            MADV_COLD,
+                            MADV_PAGEOUT,
+                        ]);
+                        prog.madvise(base, size, advice)
+                    }
+                    _ => {
+                        let new_size = Program::PAGE_SIZE as i64 * self.rng.gen_range(1..5);
+                        prog.mremap(base, size, new_size, MREMAP_MAYMOVE)
+                            .map(|_| ())
+                    }
+                }
+            }
+            SysNo::MemcpyToMap => {
+                let base = self.choose(&writable_maps(prog));
+                let size = prog.map_size(base).unwrap_or(0);
+                let length = self
+                    .rng
+                    .gen_range(0..=size.min(Program::PAGE_SIZE as i64 * 2));
+                let offset = self.rng.gen_range(0..=size - length);
+                prog.memcpy_to_map(base, offset, src, length)?;
+                // a copy to pages past the end of the mapped file dies of
+                // SIGBUS, only keep copies the model knows go through
+                match prog.syscalls.last() {
+                    Some(s) if model.copy_succeeds(s) => Ok(()),
+                    _ => Err(HdError::MapOutOfBounds {
+                        index: base,
+                        offset,
+                        length,
+                        size,
+                    }),
+                }
+            }
+            SysNo::CrashPoint => {
+                let id = prog.crash_points().len() as i64;
+                prog.crash_point(id)
+            }
+            SysNo::Fallocate => {
+                let fd = self.choose(&prog.active_file_fds);
+                let mode = self.choose(FALLOC_MODES);
+                let offset = self.choose(INTERESTING_OFFSETS);
+                let length = self.length();
+                prog.fallocate(fd, mode, offset, length)
+            }
+            SysNo::CopyFileRange => {
+                let fd_in = self.choose(&prog.active_file_fds);
+                let fd_out = self.choose(&prog.active_file_fds);
+                let length = self.length();
+                prog.copy_file_range(fd_in, fd_out, length, 0)
+            }
+            SysNo::SyncFileRange => {
+                let fd = self.choose(&prog.active_file_fds);
+                let offset = self.choose(INTERESTING_OFFSETS);
+                let length = self.length();
+                let flags = self.rng.gen_range(0..8);
+                prog.sync_file_range(fd, offset, length, flags)
+            }
+            SysNo::Fadvise64 => {
+                let fd = self.choose(&prog.active_file_fds);
+                let offset = self.choose(INTERESTING_OFFSETS);
+                let length = self.length();
+                let advice = self.rng.gen_range(POSIX_FADV_NORMAL..=POSIX_FADV_NOREUSE);
+                prog.fadvise64(fd, offset, length, advice)
+            }
+            SysNo::Readahead => {
+                let fd = self.choose(&prog.active_file_fds);
+                let offset = self.choose(INTERESTING_OFFSETS);
+                let length = self.length();
+                prog.readahead(fd, offset, length)
+            }
+            SysNo::Close => {
+                let fd = self.choose(&prog.active_fds);
+                prog.close(fd)
+            }
+            SysNo::Dup => {
+                let fd = self.choose(&prog.active_fds);
+                prog.dup(fd).map(|_| ())
+            }
+            SysNo::Dup2 | SysNo::Dup3 => {
+                let oldfd = self.choose(&prog.active_fds);
+                let newfd = self.choose(&prog.active_fds);
+                match nr {
+                    SysNo::Dup2 => prog.dup2(oldfd, newfd).map(|_| ()),
+                    _ => {
+                        let flags = self.choose(&[0, O_CLOEXEC]);
+                        prog.dup3(oldfd, newfd, flags).map(|_| ())
+                    }
+                }
+            }
+            SysNo::Chmod => {
+                let path = self.any_path(prog);
+                let mode = self.choose(MODES);
+                prog.chmod(&path, mode)
+            }
+            SysNo::Fchmod => {
+                let fd = self.choose(&prog.active_fds);
+                let mode = self.choose(MODES);
+                prog.fchmod(fd, mode)
+            }
+            SysNo::Fchmodat => {
+                let (dirfd, path) = self.at_path(prog);
+                let mode = self.choose(MODES);
+                prog.fchmodat(dirfd, &path, mode)
+            }
+            SysNo::Chown | SysNo::Lchown => {
+                let uid = self.choose(IDS);
+                let gid = self.choose(IDS);
+                match nr {
+                    SysNo::Chown => {
+                        let path = self.any_path(prog);
+                        prog.chown(&path, uid, gid)
+                    }
+                    _ => {
+                        let path = self.link_path(prog);
+                        prog.lchown(&path, uid, gid)
+                    }
+                }
+            }
+            SysNo::Fchown => {
+                let fd = self.choose(&prog.active_fds);
+                let uid = self.choose(IDS);
+                let gid = self.choose(IDS);
+                prog.fchown(fd, uid, gid)
+            }
+            SysNo::Umask => {
+                let mask = self.choose(&[0o022, 0o077, 0o000, 0o777]);
+                prog.umask(mask)
+            }
+            SysNo::Utimensat => {
+                let (dirfd, path) = self.at_path(prog);
+                let times = match self.rng.gen_bool(0.3) {
+                    true => None,
+                    false => {
+                        let mut time = || match self.rng.gen_range(0..4) {
+                            0 => (0, UTIME_NOW),
+                            1 => (0, UTIME_OMIT),
+                            _ => (
+                                self.rng.gen_range(0..1 << 31),
+                                self.rng.gen_range(0..1000000000),
+                            ),
+                        };
+                        Some([time(), time()])
+                    }
+                };
+                let flags = self.choose(&[0, AT_SYMLINK_NOFOLLOW]);
+                prog.utimensat(dirfd, &path, times, flags)
+            }
+            SysNo::Mknod | SysNo::Mknodat => {
+                let ftype = self.choose(NODE_TYPES);
+                let mode = ftype | self.choose(MODES);
+                // majors 240-254 are reserved for local use, so no driver answers
+                let dev = match ftype {
+                    S_IFCHR | S_IFBLK => {
+                        makedev(self.rng.gen_range(240..255), self.rng.gen_range(0..4))
+                    }
+                    _ => 0,
+                };
+                match nr {
+                    SysNo::Mknod => {
+                        let path = self.fresh_path(prog, "n");
+                        prog.mknod(&path, mode, dev)
+                    }
+                    _ => {
+                        let dirfd = self.choose(&prog.dirfd_choices());
+                        let path = self.fresh_name(prog, dirfd, "n");
+                        prog.mknodat(dirfd, &path, mode, dev)
+                    }
+                }
+            }
+        }
+    }
+
+    fn choose(&mut self, values: &[i64]) -> i64 {
+        *values.choose(&mut self.rng).unwrap()
+    }
+
+    // byte count that fits the 8192 byte buffers
+    fn count(&mut self) -> i64 {
+        match self.rng.gen_bool(0.7) {
+            true => self.choose(INTERESTING_LENGTHS),
+            false => self.rng.gen_range(0..=Program::PAGE_SIZE as i64 * 2),
+        }
+    }
+
+    // length not tied to a buffer
+    fn length(&mut self) -> i64 {
+        match self.rng.gen_bool(0.8) {
+            true => self.choose(INTERESTING_LENGTHS),
+            false => self.choose(INTERESTING_OFFSETS),
+        }
+    }
+
+    fn open_flags(&mut self, prog: &Program, path: &str) -> i64 {
+        match prog.find_file(path).map(|x| x.ftype) {
+            Some(FileType::Dir) => O_RDONLY | O_DIRECTORY,
+            Some(FileType::File) => self.choose(OPEN_FLAGS),
+            // a fifo, maybe behind a symlink, blocks without a writer
+            Some(_) => self.choose(OPEN_FLAGS) | O_NONBLOCK,
+            None => O_CREAT | self.choose(&[O_RDWR, O_WRONLY, O_RDWR | O_EXCL]),
+        }
+    }
+
+    // an existing path most of the time, else a new one
+    fn any_path(&mut self, prog: &Program) -> String {
+        self.path_from(prog, &prog.avail_files, "f")
+    }
+
+    fn dir_path(&mut self, prog: &Program) -> String {
+        self.path_from(prog, &prog.avail_dirs, "d")
+    }
+
+    fn non_dir_path(&mut self, prog: &Program) -> String {
+        self.path_from(prog, &prog.avail_non_dirs, "f")
+    }
+
+    // a symlink if there is one, for the l* family
+    fn link_path(&mut self, prog: &Program) -> String {
+        match prog.avail_symlinks().choose(&mut self.rng) {
+            Some(fobj) => fobj.rel_path.clone(),
+            None => self.any_path(prog),
+        }
+    }
+
+    fn path_from(&mut self, prog: &Program, fobjs: &[FileObject], stem: &str) -> String {
+        if !fobjs.is_empty() && self.rng.gen_bool(0.9) {
+            return fobjs.choose(&mut self.rng).unwrap().rel_path.clone();
+        }
+        self.fresh_path(prog, stem)
+    }
+
+    // a path nothing lives at yet, in the top directory or a known one
+    fn fresh_path(&mut self, prog: &Program, stem: &str) -> String {
+        let parent = match prog.avail_dirs.choose(&mut self.rng) {
+            Some(fobj) if self.rng.gen_bool(0.5) => format!("{}/", fobj.rel_path),
+            _ => String::new(),
+        };
+        (0..)
+            .map(|n| format!("{}{}{}", parent, stem, n))
+            .find(|x| prog.find_file(x).is_none())
+            .unwrap()
+    }
+
+    // a name nothing lives at yet, relative to dirfd
+    fn fresh_name(&mut self, prog: &Program, dirfd: i64, stem: &str) -> String {
+        (0..)
+            .map(|n| format!("{}{}", stem, n))
+            .find(|x| {
+                prog.resolve_at(dirfd, x)
+                    .is_none_or(|full| prog.find_file(&full).is_none())
+            })
+            .unwrap()
+    }
+
+    // a dirfd and an existing path relative to it, or a new name
+    fn at_path(&mut self, prog: &Program) -> (i64, String) {
+        let dirfd = self.choose(&prog.dirfd_choices());
+        let dir = match dirfd {
+            AT_FDCWD => None,
+            _ => prog.fd_path(dirfd).map(|x| format!("{}/", x)),
+        };
+        let names: Vec<String> = prog
+            .avail_files
+            .iter()
+            .filter_map(|x| match &dir {
+                Some(d) => x.rel_path.strip_prefix(d.as_str()).map(String::from),
+                None => Some(x.rel_path.clone()),
+            })
+            .collect();
+        if !names.is_empty() && self.rng.gen_bool(0.9) {
+            return (dirfd, names.choose(&mut self.rng).unwrap().clone());
+        }
+        (dirfd, self.fresh_name(prog, dirfd, "f"))
+    }
+
+    // a name the file is known to carry, or a generated one
+    fn xattr_name(&mut self, prog: &Program, path: &str) -> String {
+        let names = prog.xattr_names(path);
+        match names.choose(&mut self.rng) {
+            Some(name) if self.rng.gen_bool(0.7) => name.clone(),
+            _ => gen_xattr_name(&mut self.rng),
+        }
+    }
+}
+
+// whether prog has everything nr needs for its arguments
+fn applicable(prog: &Program, nr: SysNo) -> bool {
+    match nr {
+        SysNo::Read
+        | SysNo::Write
+        | SysNo::Pread
+        | SysNo::Pwrite
+        | SysNo::Sendfile
+        | SysNo::Ftruncate
+        | SysNo::Mmap
+        | SysNo::Fallocate
+        | SysNo::CopyFileRange
+        | SysNo::SyncFileRange
+        | SysNo::Fadvise64
+        | SysNo::Readahead => !prog.active_file_fds.is_empty(),
+        SysNo::Getdents => !prog.active_dir_fds.is_empty(),
+        SysNo::Lseek
+        | SysNo::Fstat
+        | SysNo::Fsync
+        | SysNo::Fdatasync
+        | SysNo::Syncfs
+        | SysNo::Fsetxattr
+        | SysNo::Fgetxattr
+        | SysNo::Flistxattr
+        | SysNo::Fremovexattr
+        | SysNo::Close
+        | SysNo::Dup
+        | SysNo::Dup2
+        | SysNo::Dup3
+        | SysNo::Fchmod
+        | SysNo::Fchown => !prog.active_fds.is_empty(),
+        SysNo::Munmap | SysNo::Msync | SysNo::Madvise | SysNo::Mremap => {
+            !prog.active_map_base_idx.is_empty()
+        }
+        SysNo::MemcpyToMap => !writable_maps(prog).is_empty(),
+        _ => true,
+    }
+}
+
+// what a failed add can change, cheaper to save than the whole program
+// since the variables hold the buffers
+struct Checkpoint {
+    variables: usize,
+    syscalls: usize,
+    kinds: Vec<FileType>,
+    active_fds: Vec<i64>,
+    active_file_fds: Vec<i64>,
+    active_dir_fds: Vec<i64>,
+    active_map_base_idx: Vec<i64>,
+    active_map_size: Vec<i64>,
+    avail_files: Vec<FileObject>,
+    avail_dirs: Vec<FileObject>,
+    avail_non_dirs: Vec<FileObject>,
+}
+
+impl Checkpoint {
+    fn new(prog: &Program) -> Self {
+        Self {
+            variables: prog.variables.len(),
+            syscalls: prog.syscalls.len(),
+            kinds: prog.variables.iter().map(|x| x.kind).collect(),
+            active_fds: prog.active_fds.clone(),
+            active_file_fds: prog.active_file_fds.clone(),
+            active_dir_fds: prog.active_dir_fds.clone(),
+            active_map_base_idx: prog.active_map_base_idx.clone(),
+            active_map_size: prog.active_map_size.clone(),
+            avail_files: prog.avail_files.clone(),
+            avail_dirs: prog.avail_dirs.clone(),
+            avail_non_dirs: prog.avail_non_dirs.clone(),
+        }
+    }
+
+    fn restore(self, prog: &mut Program) {
+        prog.variables.truncate(self.variables);
+        prog.syscalls.truncate(self.syscalls);
+        for (v, kind) in prog.variables.iter_mut().zip(self.kinds) {
+            v.kind = kind;
+        }
+        prog.active_fds = self.active_fds;
+        prog.active_file_fds = self.active_file_fds;
+        prog.active_dir_fds = self.active_dir_fds;
+        prog.active_map_base_idx = self.active_map_base_idx;
+        prog.active_map_size = self.active_map_size;
+        prog.avail_files = self.avail_files;
+        prog.avail_dirs = self.avail_dirs;
+        prog.avail_non_dirs = self.avail_non_dirs;
+    }
+}
+
+// active mappings made with PROT_WRITE, writing to the others faults
+fn writable_maps(prog: &Program) -> Vec<i64> {
+    let prot = |base: i64| -> Option<i64> {
+        let mut base = base;
+        // a mremap keeps the protection of the mapping it moved
+        loop {
+            let s = prog.syscalls.iter().find(|x| x.ret_index == base)?;
+            match s.nr {
+                SysNo::Mmap => return s.args.get(2)?.value,
+                SysNo::Mremap => base = arg_var(s.args.first())?,
+                _ => return None,
+            }
+        }
+    };
+    prog.active_map_base_idx
+        .iter()
+        .copied()
+        .filter(|x| prot(*x).is_some_and(|p| p & PROT_WRITE != 0))
+        .collect()
+}
+
+// the first two 8192 byte buffers, created if prog has fewer. a spliced
+// buffer has contents but is still one of them
+fn buffers(prog: &mut Program) -> (i64, i64) {
+    let size = Program::PAGE_SIZE * 2;
+    let mut found: Vec<i64> = prog
+        .variables
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| matches!(*v.var_type, VariableType::UCharPtr(_, n) if n == size))
+        .map(|(i, _)| i as i64)
+        .take(2)
+        .collect();
+    while found.len() < 2 {
+        found.push(prog.create_variable(VariableType::UCharPtr(None, size)));
+    }
+    (found[0], found[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_programs_are_valid() {
+        for seed in 0..200 {
+            let prog = ProgramGenerator::new(seed).generate(60);
+            assert_eq!(prog.validate(), vec![], "seed {}", seed);
+            assert!(prog.try_to_c().is_ok(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn extended_programs_stay_valid() {
+        let mut generator = ProgramGenerator::new(7);
+        let mut prog = generator.generate(20);
+        for _ in 0..20 {
+            generator.extend(&mut prog, 10);
+            assert_eq!(prog.validate(), vec![]);
+        }
+        assert!(prog.try_to_c().is_ok());
+    }
+
+    #[test]
+    fn weight_zero_is_never_picked() {
+        let mut generator = ProgramGenerator::new(3);
+        generator
+            .set_weight(SysNo::Open, 0)
+            .set_weight(SysNo::Openat, 0);
+        let prog = generator.generate(200);
+        assert!(prog
+            .syscalls
+            .iter()
+            .all(|x| x.nr != SysNo::Open && x.nr != SysNo::Openat));
+    }
+
+    #[test]
+    fn copies_to_maps_stay_within_the_mapped_file() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        let (src, _) = buffers(&mut prog);
+        let fd = prog.open("a", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.write(fd, src, 100).unwrap();
+        let map = Program::PAGE_SIZE as i64 * 2;
+        prog.mmap(fd, map, PROT_READ | PROT_WRITE, MAP_SHARED, 0)
+            .unwrap();
+        let mut generator = ProgramGenerator::new(1);
+        let (mut kept, mut refused) = (0, 0);
+        for _ in 0..100 {
+            let saved = Checkpoint::new(&prog);
+            match generator.add(&mut prog, SysNo::MemcpyToMap) {
+                Ok(()) => kept += 1,
+                Err(_) => {
+                    saved.restore(&mut prog);
+                    refused += 1;
+                }
+            }
+        }
+        // only the first page holds the file
+        assert!(kept > 0 && refused > 0);
+        let expected = FsModel::new().run(&prog);
+        assert!(expected.iter().all(|x| x.errno == Some(0)));
+    }
+}
@@ -1,15 +1,48 @@
 pub use crate::arg::*;
 mod arg;
+pub use crate::calls::*;
+mod calls;
+mod compact;
+mod crashpoint;
+mod crossover;
+pub use crate::depgraph::*;
+mod depgraph;
+pub use crate::error::*;
+mod error;
+pub use crate::executor::*;
+mod executor;
+pub use crate::expect::*;
+mod expect;
 pub use crate::fileobject::*;
 mod fileobject;
+pub use crate::fsmodel::*;
+mod fsmodel;
+pub use crate::generator::*;
+mod generator;
+mod minimize;
+pub use crate::mutator::*;
+mod mutator;
+pub use crate::persist::*;
+mod persist;
 pub use crate::progconstants::*;
 mod progconstants;
+pub use crate::runlog::*;
+mod runlog;
+pub use crate::signature::*;
+mod signature;
 pub use crate::syscall::*;
 mod syscall;
+// also reached through arg, kept so downstream paths do not depend on that
+#[allow(unused_imports)]
 pub use crate::types::*;
 mod types;
+pub use crate::validate::*;
+mod validate;
 pub use crate::variables::*;
 mod variables;
+pub use crate::xattrgen::*;
+mod xattrgen;
+mod xattrs;
 use serde_with::serde_as;
 use std::fmt::Write;
 use std::fs::{read_to_string, write};
@@ -29,7 +62,9 @@ pub struct Program {
     pub active_dir_fds: Vec<i64>,
     // bases of mmap'd memory
     pub active_map_base_idx: Vec<i64>,
-    // active_map_size: Vec<i64>; // should match with above
+    // mapped length of each base, should match with above
+    #[serde(default)]
+    pub active_map_size: Vec<i64>,
     pub avail_files: Vec<FileObject>,
     pub avail_dirs: Vec<FileObject>,
     pub avail_non_dirs: Vec<FileObject>,
@@ -37,11 +72,17 @@ pub struct Program {
     //#[serde(with = "tuple_vec_map")]
 }
 
+impl Default for Program {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Program {
     pub const PAGE_SIZE: u32 = 4096;
     pub const SRC8192: i64 = 0;
     pub const DEST8192: i64 = 1;
-    // v_2 .. v_n are variables for path
+    // v_2 .. v_n are variables for path, unless compact dropped the buffers
     pub const PATHSTART: i64 = 2;
     pub fn new() -> Self {
         Self {
@@ -51,6 +92,7 @@ impl Program {
             active_file_fds: Vec::<i64>::new(),
             active_dir_fds: Vec::<i64>::new(),
             active_map_base_idx: Vec::<i64>::new(),
+            active_map_size: Vec::<i64>::new(),
             avail_files: Vec::<FileObject>::new(),
             avail_dirs: Vec::<FileObject>::new(),
             avail_non_dirs: Vec::<FileObject>::new(),
@@ -89,122 +131,152 @@ impl Program {
     }
     // add file descriptor
     pub fn add_fd(&mut self, fd_index: i64) {
-        self.active_fds.push(fd_index);
+        self.try_add_fd(fd_index).expect("add_fd: invalid index");
+    }
+
+    pub fn try_add_fd(&mut self, fd_index: i64) -> Result<(), HdError> {
         // get the variable using the index and if its a directory put it with directory fds
-        let var = self.variables.get(fd_index as usize).unwrap();
-        match var.kind {
-            FileType::Dir => {
-                self.active_dir_fds.push(fd_index);
-            }
-            FileType::File | FileType::Symlink | FileType::Fifo => {
-                self.active_file_fds.push(fd_index);
-            }
-            _ => {}
+        let kind = self.try_get_variable(fd_index)?.kind;
+        self.active_fds.push(fd_index);
+        if kind == FileType::Dir {
+            self.active_dir_fds.push(fd_index);
+        } else if kind.is_non_dir() {
+            self.active_file_fds.push(fd_index);
         }
+        Ok(())
+    }
+
+    pub fn try_get_variable(&self, index: i64) -> Result<&Variable, HdError> {
+        if index < 0 {
+            return Err(HdError::DanglingIndex(index));
+        }
+        self.variables
+            .get(index as usize)
+            .ok_or(HdError::DanglingIndex(index))
     }
 
     pub fn add_variable(&mut self, v: Variable) {
         self.variables.push(v.clone());
         // if the variable is a file descriptor and its index for variables into active fds
-        match v.kind {
-            FileType::File | FileType::Dir | FileType::Symlink | FileType::Fifo => {
-                self.add_fd((self.variables.len() - 1) as i64);
-            }
-            _ => {}
+        if v.kind.is_fd_kind() {
+            self.add_fd((self.variables.len() - 1) as i64);
         }
     }
 
     pub fn remove_last_variable(&mut self) {
-        if self.variables.len() < 1 {
-            eprintln!("remove_last_variable: no more variables");
-            return;
+        if let Err(e) = self.try_remove_last_variable() {
+            eprintln!("remove_last_variable: {}", e);
         }
-        let last = self.variables.pop().unwrap();
+    }
+
+    pub fn try_remove_last_variable(&mut self) -> Result<Variable, HdError> {
+        let last = self.variables.last().ok_or(HdError::Empty("variables"))?;
         let last_index = (self.variables.len() - 1) as i64;
-        match last.kind {
-            FileType::File | FileType::Dir | FileType::Symlink | FileType::Fifo => {
-                self.remove_fd(last_index);
-            }
-            FileType::Mmap => {
-                self.mark_base_unmapped(last_index);
-            }
-            _ => {}
+        if last.kind.is_fd_kind() {
+            self.try_remove_fd(last_index)?;
+        } else if last.kind == FileType::Mmap {
+            self.try_mark_base_unmapped(last_index)?;
         }
+        self.variables.pop().ok_or(HdError::Empty("variables"))
     }
 
     pub fn add_syscall(&mut self, v: Syscall) {
         self.syscalls.push(v);
+        self.apply_xattr_syscall(self.syscalls.len() - 1);
     }
 
     pub fn remove_last_syscall(&mut self) {
-        if self.syscalls.len() < 1 {
-            eprintln!("remove_last_syscall: no more syscalls");
-            return;
+        if let Err(e) = self.try_remove_last_syscall() {
+            eprintln!("remove_last_syscall: {}", e);
         }
-        self.syscalls.pop();
+    }
+
+    pub fn try_remove_last_syscall(&mut self) -> Result<Syscall, HdError> {
+        let last = self.syscalls.pop().ok_or(HdError::Empty("syscalls"))?;
+        if last.nr.changes_xattrs() {
+            self.sync_xattrs();
+        }
+        Ok(last)
     }
 
     pub fn remove_last_syscall_if_same(&mut self, syscall: Syscall) {
-        if self.syscalls.pop().expect("remove_last_syscall_if_same") == syscall {
-            self.remove_last_syscall();
+        self.try_remove_last_syscall_if_same(syscall)
+            .expect("remove_last_syscall_if_same");
+    }
+
+    // returns whether the last syscall matched and was removed. only that
+    // one syscall is removed, the syscall before it is left alone
+    pub fn try_remove_last_syscall_if_same(&mut self, syscall: Syscall) -> Result<bool, HdError> {
+        let last = self.syscalls.last().ok_or(HdError::Empty("syscalls"))?;
+        if *last == syscall {
+            self.try_remove_last_syscall()?;
+            return Ok(true);
         }
+        Ok(false)
     }
 
     pub fn remove_syscall(&mut self, syscall: Syscall) {
         self.syscalls.retain(|x| *x != syscall);
+        if syscall.nr.changes_xattrs() {
+            self.sync_xattrs();
+        }
     }
 
     // add file object
     pub fn add_file(&mut self, fobj: FileObject, var_index: i64) {
+        if let Err(e) = self.try_add_file(fobj, var_index) {
+            eprintln!("add_file: {}", e);
+        }
+    }
+
+    pub fn try_add_file(&mut self, fobj: FileObject, var_index: i64) -> Result<(), HdError> {
+        self.try_get_variable(var_index)?;
         let mut f = fobj.clone();
         f.fd_index = var_index;
-        match f.ftype {
-            FileType::Dir => {
-                self.avail_dirs.push(fobj);
-            }
-            FileType::File | FileType::Fifo | FileType::Symlink => {
-                self.avail_non_dirs.push(fobj);
-            }
-            _ => {
-                eprintln!("add_file: invalid file object added to avail_files");
-            }
+        if f.ftype == FileType::Dir {
+            self.avail_dirs.push(fobj);
+        } else if f.ftype.is_non_dir() {
+            self.avail_non_dirs.push(fobj);
+        } else {
+            return Err(HdError::TypeMismatch {
+                index: var_index,
+                expected: "file, dir, fifo or symlink",
+                found: f.ftype,
+            });
         }
-        self.avail_files.push(f.clone());
+        self.avail_files.push(f);
+        Ok(())
     }
 
     pub fn remove_last_file(&mut self) {
+        self.try_remove_last_file().expect("remove_last_file");
+    }
+
+    pub fn try_remove_last_file(&mut self) -> Result<FileObject, HdError> {
         let fobj = self
             .avail_files
             .pop()
-            .expect("remove_last_file: avail_files has no more to remove");
-        match fobj.ftype {
-            FileType::Dir => {
-                self.avail_dirs
-                    .pop()
-                    .expect("remove_last_file: avail_dirs is empty");
-            }
-            FileType::File | FileType::Fifo | FileType::Symlink => {
-                self.avail_non_dirs
-                    .pop()
-                    .expect("remove_last_file: avail_non_dirs is empty");
-            }
-            _ => {
-                eprintln!("remove_last_file: invalid file object added to remove_last_file");
-            }
+            .ok_or(HdError::Empty("avail_files"))?;
+        if fobj.ftype == FileType::Dir {
+            self.avail_dirs.pop().ok_or(HdError::Empty("avail_dirs"))?;
+        } else if fobj.ftype.is_non_dir() {
+            self.avail_non_dirs
+                .pop()
+                .ok_or(HdError::Empty("avail_non_dirs"))?;
+        } else {
+            eprintln!("remove_last_file: invalid file object added to remove_last_file");
         }
+        Ok(fobj)
     }
+
     pub fn remove_file(&mut self, fobj: FileObject) {
         self.avail_files.retain(|x| *x != fobj);
-        match fobj.ftype {
-            FileType::Dir => {
-                self.remove_dir(fobj.clone());
-            }
-            FileType::File | FileType::Fifo | FileType::Symlink => {
-                self.remove_non_dir(fobj.clone());
-            }
-            _ => {
-                eprintln!("remove_file: invalid file object added to remove_last_file");
-            }
+        if fobj.ftype == FileType::Dir {
+            self.remove_dir(fobj.clone());
+        } else if fobj.ftype.is_non_dir() {
+            self.remove_non_dir(fobj.clone());
+        } else {
+            eprintln!("remove_file: invalid file object added to remove_last_file");
         }
     }
 
@@ -217,22 +289,25 @@ impl Program {
     }
 
     pub fn remove_fd(&mut self, fd_index: i64) {
-        let var = self
-            .variables
-            .get(fd_index as usize)
+        self.try_remove_fd(fd_index)
             .expect("remove_fd: invalid index");
-        match var.kind {
-            FileType::Dir => {
-                self.remove_dir_fd(fd_index);
-            }
-            FileType::File | FileType::Fifo | FileType::Symlink => {
-                self.remove_file_fd(fd_index);
-            }
-            _ => {
-                eprintln!("Invalid type for fd");
-            }
+    }
+
+    pub fn try_remove_fd(&mut self, fd_index: i64) -> Result<(), HdError> {
+        let var = self.try_get_variable(fd_index)?;
+        if var.kind == FileType::Dir {
+            self.remove_dir_fd(fd_index);
+        } else if var.kind.is_non_dir() {
+            self.remove_file_fd(fd_index);
+        } else {
+            return Err(HdError::TypeMismatch {
+                index: fd_index,
+                expected: "fd",
+                found: var.kind,
+            });
         }
         self.active_fds.retain(|&x| x != fd_index);
+        Ok(())
     }
 
     pub fn remove_file_fd(&mut self, fd_index: i64) {
@@ -244,23 +319,51 @@ impl Program {
     }
 
     pub fn mark_base_unmapped(&mut self, map_index: i64) {
-        let index = self
+        self.try_mark_base_unmapped(map_index)
+            .expect("mark_base_unmapped: index does not exist");
+    }
+
+    // map_index is the index of the base variable, not a position in
+    // active_map_base_idx. the mapping also stops being active, so it is
+    // not handed out to later syscalls
+    pub fn try_mark_base_unmapped(&mut self, map_index: i64) -> Result<(), HdError> {
+        let pos = self
             .active_map_base_idx
             .iter()
             .position(|x| *x == map_index)
-            .expect("mark_base_unmapped: index does not exist");
-        if let Some(var) = self.variables.get_mut(index) {
-            var.kind = FileType::None;
+            .ok_or(HdError::DanglingIndex(map_index))?;
+        self.try_get_variable(map_index)?;
+        self.active_map_base_idx.remove(pos);
+        if pos < self.active_map_size.len() {
+            self.active_map_size.remove(pos);
         }
+        self.variables[map_index as usize].kind = FileType::None;
+        Ok(())
     }
 
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, serde_json::Error> {
-        let file = read_to_string(path).unwrap();
-        serde_json::from_str(&file)
+    // record a new mapping of length bytes based at map_index
+    pub fn add_map_base(&mut self, map_index: i64, length: i64) {
+        self.active_map_base_idx.push(map_index);
+        self.active_map_size.push(length);
     }
 
-    pub fn from_str(s: String) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(&s)
+    // mapped length of the base at map_index, if it is still mapped
+    pub fn map_size(&self, map_index: i64) -> Option<i64> {
+        let pos = self
+            .active_map_base_idx
+            .iter()
+            .position(|x| *x == map_index)?;
+        self.active_map_size.get(pos).copied()
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, HdError> {
+        let file = read_to_string(path)?;
+        Ok(serde_json::from_str(&file)?)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: String) -> Result<Self, HdError> {
+        Ok(serde_json::from_str(&s)?)
     }
 
     pub fn to_path<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
@@ -272,8 +375,173 @@ impl Program {
     pub fn cprogram_to_file<P: AsRef<Path>>(&self, path: &mut P) -> std::io::Result<()> {
         write(path, format!("{}", self))
     }
+
+    pub fn try_cprogram_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), HdError> {
+        write(path, self.try_to_c()?)?;
+        Ok(())
+    }
+
+    pub fn try_cprogram_to_file_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        options: &COptions,
+    ) -> Result<(), HdError> {
+        write(path, self.try_to_c_with(options)?)?;
+        Ok(())
+    }
+
+    // C source for the whole program
+    pub fn try_to_c(&self) -> Result<String, HdError> {
+        self.try_to_c_with(&COptions::default())
+    }
+
+    // C source that also compares the result of each syscall with its
+    // expected_ret and expected_errno
+    pub fn try_to_checked_c(&self) -> Result<String, HdError> {
+        self.try_to_c_with(&COptions {
+            check: true,
+            ..COptions::default()
+        })
+    }
+
+    pub fn try_to_c_with(&self, options: &COptions) -> Result<String, HdError> {
+        options.check_paths()?;
+        let mut output = get_headers();
+        let crash = !self.crash_points().is_empty();
+        if let (true, CrashHook::Function(name)) = (crash, &options.crash) {
+            write!(&mut output, "\nvoid {}(long id);\n", name).unwrap();
+        }
+        output.push_str("\nint main(int argc, char* argv[])\n{\n");
+        for v in self.variables.iter() {
+            writeln!(&mut output, "{}", v.try_to_c()?).unwrap();
+        }
+        if crash {
+            output.push_str("\tlong __crash_at = argc > 1 ? atol(argv[1]) : -1;\n");
+        }
+        if options.wraps() {
+            output.push_str("\tlong __ret;\n\tint __errno;\n");
+        }
+        match &options.log {
+            Some(LogTarget::Stderr) => output.push_str("\tint __log = 2;\n"),
+            // moved to a high fd so that the fds of the program stay the same
+            Some(LogTarget::File(path)) => writeln!(
+                &mut output,
+                "\tint __log = open(\"{}\", O_WRONLY | O_CREAT | O_TRUNC, 0644);\n\
+                 \tif (__log < 0)\n\t\t__log = 2;\n\
+                 \telse {{\n\t\tint __fd = fcntl(__log, F_DUPFD_CLOEXEC, {});\n\
+                 \t\tclose(__log);\n\t\t__log = __fd < 0 ? 2 : __fd;\n\t}}",
+                c_escape(path.as_bytes()),
+                LOG_FD_MIN
+            )
+            .unwrap(),
+            None => {}
+        }
+        output.push('\n');
+        for (pos, s) in self.syscalls.iter().enumerate() {
+            let syscall_print_out = match (s.nr, options.wraps()) {
+                (SysNo::CrashPoint, _) => try_get_crash_point(self, s, &options.crash)?,
+                (_, true) => try_get_wrapped_syscall(self, pos, s, options)?,
+                (_, false) => try_get_syscall(self, s)?,
+            };
+            write!(&mut output, "{} ", syscall_print_out).unwrap();
+        }
+        output.push('\n');
+        for fd_index in self.active_fds.iter() {
+            let var = self.try_get_variable(*fd_index)?;
+            writeln!(&mut output, "\tclose({});", var.name).unwrap();
+        }
+        output.push_str("\treturn 0;\n");
+        output.push_str("}\n");
+        output.push_str("/* Active fds: ");
+        for fd_index in self.active_fds.iter() {
+            let var = self.try_get_variable(*fd_index)?;
+            write!(&mut output, "{} ", var.name).unwrap();
+        }
+        output.push_str("*/\n/*Files\n");
+        for fobj in self.avail_files.iter() {
+            let var = self.try_get_variable(fobj.fd_index)?;
+            let path = match &*var.var_type {
+                VariableType::Str(s) => s.clone(),
+                _ => String::from(
+                    "wrong index provided to variable vector fmt::Display avail_files.iter()",
+                ),
+            };
+            writeln!(
+                &mut output,
+                "{:?}",
+                CString::new(path).map_err(|_| HdError::Unprintable(var.name.clone()))?
+            )
+            .unwrap();
+        }
+        output.push_str("*/\n");
+        Ok(output)
+    }
+}
+
+// what the C backend adds around the syscalls
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct COptions {
+    // report every syscall whose result differs from what it expects
+    pub check: bool,
+    // write one json line per syscall with its arguments and result
+    pub log: Option<LogTarget>,
+    // what the crash points do
+    pub crash: CrashHook,
+}
+
+impl COptions {
+    // whether the result of each syscall is kept for the wrappers
+    fn wraps(&self) -> bool {
+        self.check || self.log.is_some()
+    }
+
+    // the files the program writes on the side must live outside the
+    // directory it runs in, or they show up in its listings
+    fn check_paths(&self) -> Result<(), HdError> {
+        let paths = [
+            match &self.log {
+                Some(LogTarget::File(path)) => Some(path),
+                _ => None,
+            },
+            match &self.crash {
+                CrashHook::ControlFile(path) => Some(path),
+                _ => None,
+            },
+        ];
+        match paths.into_iter().flatten().find(|x| !x.starts_with('/')) {
+            Some(path) => Err(HdError::RelativePath(path.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+// where the C program writes its log, a file must be given by an absolute
+// path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogTarget {
+    Stderr,
+    File(String),
+}
+
+// what a crash point does when the program reaches it. a program run with
+// an id as its first argument only runs the hook at the crash point with
+// that id. without one, the hooks that go on run at every crash point and
+// SyncAbort at none, so the program runs to the end
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CrashHook {
+    // flush everything to disk and abort
+    #[default]
+    SyncAbort,
+    // append the id as a line to the file and go on, the path must be
+    // absolute as for LogTarget::File
+    ControlFile(String),
+    // call the function void name(long id) the harness links in and go on
+    Function(String),
 }
 
+// lowest fd the log file is moved to
+const LOG_FD_MIN: i32 = 512;
+
 pub fn get_headers() -> String {
     String::from(
         "#define _GNU_SOURCE\n\
@@ -287,6 +555,7 @@ pub fn get_headers() -> String {
 #include <errno.h>\n\
 //#include <error.h>\n\
 #include <fcntl.h>\n\
+#include <linux/falloc.h>\n\
 #include <stdio.h>\n\
 #include <stdlib.h>\n\
 #include <string.h>\n\
@@ -295,78 +564,258 @@ pub fn get_headers() -> String {
 }
 
 pub fn get_syscall(prog: &Program, syscall: &Syscall) -> String {
-    let mut output = String::from("");
+    try_get_syscall(prog, syscall).expect("get_syscall")
+}
+
+pub fn try_get_syscall(prog: &Program, syscall: &Syscall) -> Result<String, HdError> {
+    match syscall.nr {
+        SysNo::MemcpyToMap => return try_get_memcpy_to_map(prog, syscall),
+        SysNo::CrashPoint => return try_get_crash_point(prog, syscall, &CrashHook::default()),
+        _ => {}
+    }
+    let call = try_get_call(prog, syscall)?;
     // why -1
-    let sys_name = num_to_name(syscall.nr);
     if syscall.ret_index != -1 {
-        let index = syscall.ret_index as usize;
-        let name = &prog.variables[index].name;
-        write!(&mut output, "\t{name} = syscall({sys_name}").unwrap();
-    } else {
-        write!(&mut output, "\tsyscall({sys_name}").unwrap();
+        let var = prog.try_get_variable(syscall.ret_index)?;
+        let name = &var.name;
+        if matches!(
+            *var.var_type,
+            VariableType::VoidPtr | VariableType::MmapBase
+        ) {
+            return Ok(format!("\t{name} = (void *){call};\n"));
+        }
+        return Ok(format!("\t{name} = {call};\n"));
     }
-    for arg in syscall.args.iter() {
+    Ok(format!("\t{call};\n"))
+}
+
+// syscall(SYS_x, args) without the assignment
+fn try_get_call(prog: &Program, syscall: &Syscall) -> Result<String, HdError> {
+    let mut output = format!("syscall({}", num_to_name(syscall.nr));
+    let sig = signature(syscall.nr);
+    for (pos, arg) in syscall.args.iter().enumerate() {
         if arg.is_variable {
-            let index = arg
-                .index
-                .expect("get_syscall found a value when expecting an index");
-            let name = &prog.variables[index].name;
+            let index = arg.index.ok_or(HdError::MalformedArg(pos))?;
+            let name = &prog.try_get_variable(index as i64)?.name;
             write!(&mut output, ", (long){name}").unwrap();
         } else {
-            let value = arg
-                .value
-                .expect("get_syscall found an index when expecting a value");
-            write!(&mut output, ", {value}").unwrap();
+            let value = arg.value.ok_or(HdError::MalformedArg(pos))?;
+            if sig.get(pos) == Some(&ArgKind::DirFd) && value == AT_FDCWD {
+                output.push_str(", AT_FDCWD");
+            } else if sig.get(pos) == Some(&ArgKind::FallocMode) {
+                write!(&mut output, ", {}", falloc_mode_to_c(value)).unwrap();
+            } else {
+                write!(&mut output, ", {}", value_to_c(value)).unwrap();
+            }
         }
     }
-    output.push_str(");\n");
-    output
+    output.push(')');
+    Ok(output)
 }
 
-impl fmt::Display for Program {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut output = get_headers();
-        output.push_str("\nint main(int argc, char* argv[])\n{\n");
-        for v in self.variables.iter() {
-            write!(&mut output, "{}\n", *v).unwrap();
+// the syscall with errno cleared before, and its result logged and compared
+// after as options ask
+fn try_get_wrapped_syscall(
+    prog: &Program,
+    pos: usize,
+    syscall: &Syscall,
+    options: &COptions,
+) -> Result<String, HdError> {
+    let mut output = try_get_syscall(prog, syscall)?;
+    if syscall.nr.is_pseudo() {
+        return Ok(output);
+    }
+    let ret = match syscall.ret_index {
+        -1 => {
+            output = format!("\t__ret = {};\n", try_get_call(prog, syscall)?);
+            String::from("__ret")
         }
-        for s in self.syscalls.iter() {
-            let syscall_print_out = get_syscall(self, s);
-            write!(&mut output, "{} ", syscall_print_out).unwrap();
+        index => format!("(long){}", prog.try_get_variable(index)?.name),
+    };
+    output.insert_str(0, "\terrno = 0;\n");
+    output.push_str("\t__errno = errno;\n");
+    if options.log.is_some() {
+        output.push_str(&try_get_log(prog, pos, syscall, &ret)?);
+    }
+    if !options.check {
+        return Ok(output);
+    }
+    let mut conds = Vec::<String>::new();
+    if let Some(value) = syscall.expected_ret {
+        conds.push(format!("{} != {}", ret, value_to_c(value)));
+    }
+    if let Some(errno) = syscall.expected_errno {
+        conds.push(format!("__errno != {}", errno));
+    }
+    if conds.is_empty() {
+        return Ok(output);
+    }
+    let report = format!(
+        "{} index={} expected_ret={} expected_errno={}",
+        MISMATCH_TAG,
+        pos,
+        show_expected(syscall.expected_ret),
+        show_expected(syscall.expected_errno)
+    );
+    writeln!(
+        &mut output,
+        "\tif ({})\n\t\tfprintf(stderr, \"{} ret=%ld errno=%d\\n\", {}, __errno);",
+        conds.join(" || "),
+        report,
+        ret
+    )
+    .unwrap();
+    Ok(output)
+}
+
+// dprintf of the log line of the syscall at pos. paths are known here and
+// written as strings, other variables are printed as numbers at run time
+fn try_get_log(
+    prog: &Program,
+    pos: usize,
+    syscall: &Syscall,
+    ret: &str,
+) -> Result<String, HdError> {
+    let name = num_to_name(syscall.nr);
+    let mut line = format!(
+        "{{\"index\":{},\"name\":\"{}\",\"args\":[",
+        pos,
+        name.trim_start_matches("SYS_")
+    );
+    let mut values = Vec::<String>::new();
+    for (k, arg) in syscall.args.iter().enumerate() {
+        if k > 0 {
+            line.push(',');
         }
-        output.push_str("\n");
-        for fd_index in self.active_fds.iter() {
-            let var = self.variables.get(*fd_index as usize).unwrap();
-            write!(&mut output, "\tclose({});\n", var.name).unwrap();
+        if arg.is_variable {
+            let index = arg.index.ok_or(HdError::MalformedArg(k))?;
+            let var = prog.try_get_variable(index as i64)?;
+            match &*var.var_type {
+                VariableType::Str(path) => {
+                    line.push_str(&serde_json::to_string(path)?.replace('%', "%%"))
+                }
+                _ => {
+                    line.push_str("%ld");
+                    values.push(format!("(long){}", var.name));
+                }
+            }
+        } else {
+            let value = arg.value.ok_or(HdError::MalformedArg(k))?;
+            line.push_str(&value.to_string());
         }
-        output.push_str("\treturn 0;\n");
-        output.push_str("}\n");
-        output.push_str("/* Active fds: ");
-        for fd_index in self.active_fds.iter() {
-            let var = self.variables.get(*fd_index as usize).unwrap();
-            write!(&mut output, "{} ", var.name).unwrap();
+    }
+    line.push_str("],\"ret\":%ld,\"errno\":%d}\n");
+    values.push(String::from(ret));
+    values.push(String::from("__errno"));
+    Ok(format!(
+        "\tdprintf(__log, \"{}\", {});\n",
+        c_escape(line.as_bytes()),
+        values.join(", ")
+    ))
+}
+
+// an expected value in a mismatch report, * if it is not checked
+fn show_expected(value: Option<i64>) -> String {
+    value.map_or(String::from("*"), |x| x.to_string())
+}
+
+// fallocate mode as an or of FALLOC_FL_* names
+fn falloc_mode_to_c(mode: i64) -> String {
+    let names = [
+        (FALLOC_FL_KEEP_SIZE, "FALLOC_FL_KEEP_SIZE"),
+        (FALLOC_FL_PUNCH_HOLE, "FALLOC_FL_PUNCH_HOLE"),
+        (FALLOC_FL_NO_HIDE_STALE, "FALLOC_FL_NO_HIDE_STALE"),
+        (FALLOC_FL_COLLAPSE_RANGE, "FALLOC_FL_COLLAPSE_RANGE"),
+        (FALLOC_FL_ZERO_RANGE, "FALLOC_FL_ZERO_RANGE"),
+        (FALLOC_FL_INSERT_RANGE, "FALLOC_FL_INSERT_RANGE"),
+        (FALLOC_FL_UNSHARE_RANGE, "FALLOC_FL_UNSHARE_RANGE"),
+    ];
+    let mut parts = Vec::<String>::new();
+    let mut rest = mode;
+    for (bit, name) in names {
+        if mode & bit != 0 {
+            parts.push(String::from(name));
+            rest &= !bit;
         }
-        output.push_str("*/\n/*Files\n");
-        for fobj in self.avail_files.iter() {
-            let index = fobj.fd_index;
-            let var = match &*self
-                .variables
-                .get(index as usize)
-                .expect("no such index in variable vector fmt::Display avail_files.iter()")
-                .var_type
-            {
-                VariableType::Str(s) => s.clone(),
-                _ => String::from(
-                    "wrong index provided to variable vector fmt::Display avail_files.iter()",
-                ),
-            };
-            write!(
-                &mut output,
-                "{:?}\n",
-                CString::new(var.clone()).expect("converting file name for C printing failed")
-            )
-            .unwrap();
+    }
+    if rest != 0 || parts.is_empty() {
+        parts.push(value_to_c(rest));
+    }
+    parts.join(" | ")
+}
+
+// C has no literal for i64::MIN, -9223372036854775808 negates a constant
+// that does not fit in long
+fn value_to_c(value: i64) -> String {
+    match value {
+        i64::MIN => String::from("(-9223372036854775807L - 1)"),
+        _ => value.to_string(),
+    }
+}
+
+// the hook of a crash point, run if it is the one asked for on the command
+// line, or for hooks that go on, if none was
+fn try_get_crash_point(
+    prog: &Program,
+    syscall: &Syscall,
+    hook: &CrashHook,
+) -> Result<String, HdError> {
+    let arg = syscall.args.first().ok_or(HdError::MalformedArg(0))?;
+    let id = match arg.is_variable {
+        true => {
+            let index = arg.index.ok_or(HdError::MalformedArg(0))?;
+            prog.try_get_variable(index as i64)?.name.clone()
         }
-        write!(f, "{}*/\n", output)
+        false => value_to_c(arg.value.ok_or(HdError::MalformedArg(0))?),
+    };
+    let body = match hook {
+        CrashHook::SyncAbort => String::from("\t\tsync();\n\t\tabort();\n"),
+        CrashHook::ControlFile(path) => format!(
+            "\t\tint __fd = open(\"{}\", O_WRONLY | O_CREAT | O_APPEND | O_CLOEXEC, 0644);\n\
+             \t\tif (__fd >= 0) {{\n\t\t\tdprintf(__fd, \"%ld\\n\", (long){});\n\
+             \t\t\tclose(__fd);\n\t\t}}\n",
+            c_escape(path.as_bytes()),
+            id
+        ),
+        CrashHook::Function(name) => format!("\t\t{}({});\n", name, id),
+    };
+    let cond = match hook {
+        CrashHook::SyncAbort => format!("__crash_at >= 0 && __crash_at == {}", id),
+        _ => format!("__crash_at < 0 || __crash_at == {}", id),
+    };
+    Ok(format!("\tif ({}) {{\n{}\t}}\n", cond, body))
+}
+
+// memcpy(map + offset, src, len)
+fn try_get_memcpy_to_map(prog: &Program, syscall: &Syscall) -> Result<String, HdError> {
+    let mut parts = Vec::<String>::new();
+    for (pos, arg) in syscall.args.iter().enumerate() {
+        if arg.is_variable {
+            let index = arg.index.ok_or(HdError::MalformedArg(pos))?;
+            parts.push(prog.try_get_variable(index as i64)?.name.clone());
+        } else {
+            let value = arg.value.ok_or(HdError::MalformedArg(pos))?;
+            parts.push(value_to_c(value));
+        }
+    }
+    if parts.len() != 4 {
+        return Err(HdError::ArityMismatch {
+            nr: syscall.nr,
+            expected: 4,
+            found: parts.len(),
+        });
+    }
+    // a failed mmap leaves MAP_FAILED behind, skip the copy instead of
+    // crashing on it
+    Ok(format!(
+        "\tif ({} != MAP_FAILED)\n\t\tmemcpy((char *){} + {}, {}, {});\n",
+        parts[0], parts[0], parts[1], parts[2], parts[3]
+    ))
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let output = self.try_to_c().map_err(|_| fmt::Error)?;
+        write!(f, "{}", output)
     }
 }
@@ -0,0 +1,292 @@
+use crate::*;
+
+impl Program {
+    // the smallest program found that still satisfies pred: syscalls are
+    // removed in shrinking chunks (delta debugging), then unreferenced
+    // variables, then byte buffers are cleared and shrunk. every candidate
+    // passed to pred is valid. returns a copy of self if pred does not hold
+    pub fn minimize<F: FnMut(&Program) -> bool>(&self, mut pred: F) -> Program {
+        let mut prog = self.clone();
+        if !pred(&prog) {
+            return prog;
+        }
+        loop {
+            let mut changed = prog.minimize_syscalls(&mut pred);
+            changed |= prog.minimize_variables(&mut pred);
+            changed |= prog.minimize_buffers(&mut pred);
+            if !changed {
+                return prog;
+            }
+        }
+    }
+
+    // whether any syscall, list or file object refers to the variable at index
+    pub fn is_referenced(&self, index: i64) -> bool {
+        self.syscalls
+            .iter()
+            .any(|s| s.ret_index == index || s.args.iter().any(|x| arg_var(Some(x)) == Some(index)))
+            || self.active_fds.contains(&index)
+            || self.active_file_fds.contains(&index)
+            || self.active_dir_fds.contains(&index)
+            || self.active_map_base_idx.contains(&index)
+            || self
+                .avail_files
+                .iter()
+                .chain(self.avail_dirs.iter())
+                .chain(self.avail_non_dirs.iter())
+                .any(|x| x.fd_index == index)
+    }
+
+    // remove the variable at index, which nothing may refer to
+    pub fn remove_variable(&mut self, index: i64) -> Result<Variable, HdError> {
+        let v = self.try_get_variable(index)?.clone();
+        if self.is_referenced(index) {
+            return Err(HdError::InUse(index));
+        }
+        let map: Vec<Option<i64>> = (0..self.variables.len() as i64)
+            .map(|i| match i.cmp(&index) {
+                std::cmp::Ordering::Less => Some(i),
+                std::cmp::Ordering::Equal => None,
+                std::cmp::Ordering::Greater => Some(i - 1),
+            })
+            .collect();
+        self.renumber_variables(&map)?;
+        Ok(v)
+    }
+
+    // move variable i to index map[i], dropping it if that is None. variables
+    // named after their index are renamed, every reference is rewritten
+    pub(crate) fn renumber_variables(&mut self, map: &[Option<i64>]) -> Result<(), HdError> {
+        let index_of = |index: i64| -> Result<i64, HdError> {
+            map.get(index as usize)
+                .copied()
+                .flatten()
+                .ok_or(HdError::DanglingIndex(index))
+        };
+        for s in self.syscalls.iter_mut() {
+            if s.ret_index != -1 {
+                s.ret_index = index_of(s.ret_index)?;
+            }
+            for arg in s.args.iter_mut() {
+                if let Some(index) = arg_var(Some(arg)) {
+                    *arg = Arg::new(index_of(index)?, true);
+                }
+            }
+        }
+        let lists = [
+            &mut self.active_fds,
+            &mut self.active_file_fds,
+            &mut self.active_dir_fds,
+            &mut self.active_map_base_idx,
+        ];
+        for list in lists {
+            for index in list.iter_mut() {
+                *index = index_of(*index)?;
+            }
+        }
+        let lists = [
+            &mut self.avail_files,
+            &mut self.avail_dirs,
+            &mut self.avail_non_dirs,
+        ];
+        for list in lists {
+            for fobj in list.iter_mut() {
+                fobj.fd_index = index_of(fobj.fd_index)?;
+            }
+        }
+        let mut variables = Vec::<Variable>::with_capacity(self.variables.len());
+        for (old, mut v) in self.variables.drain(..).enumerate() {
+            if let Some(new) = map.get(old).copied().flatten() {
+                if v.name == format!("v{}", old) {
+                    v.name = format!("v{}", new);
+                }
+                variables.push(v);
+            }
+        }
+        self.variables = variables;
+        Ok(())
+    }
+
+    // bytes the variable at index must hold for every syscall that uses it,
+    // None if some use is not bounded by a length argument
+    pub fn required_capacity(&self, index: i64) -> Option<i64> {
+        let mut need = 0;
+        for s in self.syscalls.iter() {
+            let sig = signature(s.nr);
+            for (k, arg) in s.args.iter().enumerate() {
+                if arg_var(Some(arg)) != Some(index) {
+                    continue;
+                }
+                let length = match (s.nr, sig.get(k), sig.get(k + 1)) {
+                    (SysNo::MemcpyToMap, _, _) if k == 2 => s.args.get(3)?.value?,
+                    (_, Some(ArgKind::Buffer), Some(ArgKind::Length)) => s.args.get(k + 1)?.value?,
+                    // e.g. a struct stat
+                    _ => return None,
+                };
+                need = need.max(length);
+            }
+        }
+        Some(need)
+    }
+
+    fn accept<F: FnMut(&Program) -> bool>(&mut self, candidate: Program, pred: &mut F) -> bool {
+        if candidate.is_valid() && candidate.check_def_order().is_ok() && pred(&candidate) {
+            *self = candidate;
+            return true;
+        }
+        false
+    }
+
+    // remove chunks of syscalls, halving the chunk size whenever no chunk
+    // can go
+    fn minimize_syscalls<F: FnMut(&Program) -> bool>(&mut self, pred: &mut F) -> bool {
+        let mut changed = false;
+        let mut chunk = (self.syscalls.len() / 2).max(1);
+        loop {
+            let mut removed = false;
+            let mut end = self.syscalls.len();
+            // later syscalls first, removing them does not disturb earlier ones
+            while end > 0 {
+                let start = end.saturating_sub(chunk);
+                let mut candidate = self.clone();
+                let mut ok = true;
+                for pos in (start..end).rev() {
+                    if pos < candidate.syscalls.len() && candidate.delete_syscall(pos).is_err() {
+                        ok = false;
+                        break;
+                    }
+                }
+                if ok && self.accept(candidate, pred) {
+                    removed = true;
+                }
+                end = start;
+            }
+            changed |= removed;
+            if !removed {
+                if chunk == 1 {
+                    return changed;
+                }
+                chunk /= 2;
+            }
+        }
+    }
+
+    // drop variables nothing refers to, all at once if possible
+    fn minimize_variables<F: FnMut(&Program) -> bool>(&mut self, pred: &mut F) -> bool {
+        let unused: Vec<i64> = (0..self.variables.len() as i64)
+            .filter(|x| !self.is_referenced(*x))
+            .collect();
+        if unused.is_empty() {
+            return false;
+        }
+        let mut candidate = self.clone();
+        for index in unused.iter().rev() {
+            if candidate.remove_variable(*index).is_err() {
+                return false;
+            }
+        }
+        if self.accept(candidate, pred) {
+            return true;
+        }
+        let mut changed = false;
+        for index in unused.iter().rev() {
+            let mut candidate = self.clone();
+            if candidate.remove_variable(*index).is_ok() && self.accept(candidate, pred) {
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // clear, halve and finally shrink the byte buffers
+    fn minimize_buffers<F: FnMut(&Program) -> bool>(&mut self, pred: &mut F) -> bool {
+        let mut changed = false;
+        for index in 0..self.variables.len() {
+            let (value, size) = match &*self.variables[index].var_type {
+                VariableType::UCharPtr(v, size) => (v.clone(), *size),
+                _ => continue,
+            };
+            let set = |prog: &Program, value: Option<Vec<u8>>, size: u32| {
+                let mut candidate = prog.clone();
+                *candidate.variables[index].var_type = VariableType::UCharPtr(value, size);
+                // xattr values live in byte buffers
+                candidate.sync_xattrs();
+                candidate
+            };
+            let mut value = value;
+            if let Some(bytes) = value.clone() {
+                if self.accept(set(self, None, size), pred) {
+                    value = None;
+                    changed = true;
+                } else {
+                    let mut bytes = bytes;
+                    while !bytes.is_empty() {
+                        let shorter = bytes[..bytes.len() / 2].to_vec();
+                        if !self.accept(set(self, Some(shorter.clone()), size), pred) {
+                            break;
+                        }
+                        bytes = shorter;
+                        changed = true;
+                    }
+                    value = Some(bytes);
+                }
+            }
+            let need = match self.required_capacity(index as i64) {
+                Some(n) => n.max(value.as_ref().map_or(0, |v| v.len() as i64)),
+                None => continue,
+            };
+            if need < size as i64 && self.accept(set(self, value, need as u32), pred) {
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nrs(prog: &Program) -> Vec<SysNo> {
+        prog.syscalls.iter().map(|x| x.nr).collect()
+    }
+
+    #[test]
+    fn keeps_what_the_predicate_needs() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.write(fd, Program::SRC8192, 100).unwrap();
+        prog.mkdir("d", 0o755).unwrap();
+        prog.fsync(fd).unwrap();
+        prog.close(fd).unwrap();
+        let writes = |p: &Program| p.syscalls.iter().any(|x| x.nr == SysNo::Write);
+        let small = prog.minimize(writes);
+        assert!(writes(&small));
+        assert_eq!(small.validate(), vec![]);
+        // the write needs the fd the open assigned
+        assert_eq!(nrs(&small), vec![SysNo::Open, SysNo::Write]);
+        // only the source buffer is left, holding the 100 bytes written
+        assert!(small.variables.iter().all(|v| match &*v.var_type {
+            VariableType::UCharPtr(_, size) => *size == 100,
+            _ => true,
+        }));
+        assert!(small.variables.len() < prog.variables.len());
+    }
+
+    #[test]
+    fn generated_programs_shrink_to_a_valid_witness() {
+        for seed in 0..10 {
+            let prog = ProgramGenerator::new(seed).generate(40);
+            let renames = |p: &Program| p.syscalls.iter().any(|x| x.nr == SysNo::Rename);
+            let small = prog.minimize(renames);
+            assert_eq!(small.validate(), vec![], "seed {}", seed);
+            if renames(&prog) {
+                assert!(renames(&small));
+                assert_eq!(nrs(&small), vec![SysNo::Rename], "seed {}", seed);
+            } else {
+                assert_eq!(small.syscalls.len(), prog.syscalls.len());
+            }
+        }
+    }
+}
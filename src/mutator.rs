@@ -0,0 +1,660 @@
+use crate::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+// replacement values for integer arguments
+pub const INTERESTING_VALUES: &[i64] = &[
+    0,
+    1,
+    -1,
+    2,
+    511,
+    512,
+    4095,
+    4096,
+    4097,
+    8191,
+    8192,
+    0x7fff,
+    0xffff,
+    0x7fffffff,
+    0x80000000,
+    0xffffffff,
+    1 << 32,
+    i64::MAX,
+    i64::MIN,
+];
+
+// an edit applied by Mutator, positions refer to the program after the edit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mutation {
+    Insert {
+        pos: usize,
+        nr: SysNo,
+    },
+    // removed counts the syscall at pos plus the uses that could not be repaired
+    Delete {
+        pos: usize,
+        nr: SysNo,
+        removed: usize,
+    },
+    Swap {
+        a: usize,
+        b: usize,
+    },
+    Arg {
+        syscall: usize,
+        arg: usize,
+        old: i64,
+        new: i64,
+    },
+    // removed bytes at offset of the buffer variable were replaced by inserted bytes
+    Splice {
+        index: i64,
+        offset: usize,
+        removed: usize,
+        inserted: usize,
+    },
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mutation::Insert { pos, nr } => write!(f, "insert {} at {}", nr, pos),
+            Mutation::Delete { pos, nr, removed } => {
+                write!(f, "delete {} at {} ({} syscalls removed)", nr, pos, removed)
+            }
+            Mutation::Swap { a, b } => write!(f, "swap {} and {}", a, b),
+            Mutation::Arg {
+                syscall,
+                arg,
+                old,
+                new,
+            } => write!(
+                f,
+                "syscall {} argument {} from {} to {}",
+                syscall, arg, old, new
+            ),
+            Mutation::Splice {
+                index,
+                offset,
+                removed,
+                inserted,
+            } => write!(
+                f,
+                "splice {} bytes over {} at offset {} of variable {}",
+                inserted, removed, offset, index
+            ),
+        }
+    }
+}
+
+impl Program {
+    // position of the first syscall that assigns variable index
+    pub fn definition(&self, index: i64) -> Option<usize> {
+        self.syscalls.iter().position(|x| x.ret_index == index)
+    }
+
+    // every variable argument must be assigned before it is used, variables
+    // no syscall assigns are initialized in their declaration
+    pub fn check_def_order(&self) -> Result<(), HdError> {
+        let mut defs = HashMap::<i64, usize>::new();
+        for (pos, s) in self.syscalls.iter().enumerate() {
+            if s.ret_index != -1 {
+                defs.entry(s.ret_index).or_insert(pos);
+            }
+        }
+        for (pos, s) in self.syscalls.iter().enumerate() {
+            for index in s.args.iter().filter_map(|x| arg_var(Some(x))) {
+                if defs.get(&index).is_some_and(|d| *d >= pos) {
+                    return Err(HdError::UseBeforeDef {
+                        syscall: pos,
+                        index,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // remove the syscall at pos; later uses of the variable it assigned are
+    // pointed at the closest earlier variable that fits, or removed as well.
+    // what the removed syscalls closed, unmapped, unlinked or renamed is
+    // back afterwards. returns how many syscalls were removed
+    pub fn delete_syscall(&mut self, pos: usize) -> Result<usize, HdError> {
+        let count = self.remove_syscall_at(pos)?;
+        self.sync_state()?;
+        Ok(count)
+    }
+
+    fn remove_syscall_at(&mut self, pos: usize) -> Result<usize, HdError> {
+        if pos >= self.syscalls.len() {
+            return Err(HdError::NoSyscall(pos));
+        }
+        let removed = self.syscalls.remove(pos);
+        let mut count = 1;
+        let ret = removed.ret_index;
+        if ret != -1 {
+            // uses before a later assignment are left without a value
+            let mut j = pos;
+            while j < self.definition(ret).unwrap_or(self.syscalls.len()) {
+                let uses: Vec<usize> = self.syscalls[j]
+                    .args
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, x)| arg_var(Some(x)) == Some(ret))
+                    .map(|(k, _)| k)
+                    .collect();
+                if uses.is_empty() {
+                    j += 1;
+                    continue;
+                }
+                match self.substitute(ret, j, &uses) {
+                    Some(other) => {
+                        for k in uses {
+                            self.syscalls[j].args[k] = Arg::new(other, true);
+                        }
+                        j += 1;
+                    }
+                    None => count += self.remove_syscall_at(j)?,
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    // base of a memcpy_to_map after the mapping was unmapped or moved, the
+    // copy would fault in the C program
+    fn copy_to_unmapped(&self) -> Option<i64> {
+        let mut unmapped = Vec::<i64>::new();
+        for s in self.syscalls.iter() {
+            let base = arg_var(s.args.first());
+            match s.nr {
+                SysNo::Munmap | SysNo::Mremap => unmapped.extend(base),
+                SysNo::MemcpyToMap if base.is_some_and(|x| unmapped.contains(&x)) => return base,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // bring the active lists and file objects in line with the syscalls
+    // after they were edited in the middle
+    pub(crate) fn sync_state(&mut self) -> Result<(), HdError> {
+        // the file objects are only as good as the model, keep them when
+        // it lost track. the fd kinds it sets decide the active lists
+        self.simulate()?;
+        self.sync_active()?;
+        self.sync_xattrs();
+        Ok(())
+    }
+
+    // rebuild the active fd and map lists from the syscalls, the way the
+    // builders keep them while appending
+    pub fn sync_active(&mut self) -> Result<(), HdError> {
+        self.active_fds.clear();
+        self.active_file_fds.clear();
+        self.active_dir_fds.clear();
+        self.active_map_base_idx.clear();
+        self.active_map_size.clear();
+        // bases count as mapped again once their mmap or mremap is replayed
+        for v in self
+            .variables
+            .iter_mut()
+            .filter(|x| x.kind == FileType::Mmap)
+        {
+            v.kind = FileType::None;
+        }
+        for j in 0..self.syscalls.len() {
+            let s = &self.syscalls[j];
+            let (nr, ret) = (s.nr, s.ret_index);
+            let first = arg_var(s.args.first());
+            let second = arg_var(s.args.get(1));
+            let length = |k: usize| s.args.get(k).and_then(|x| x.value).unwrap_or(0);
+            let (map_len, remap_len) = (length(1), length(2));
+            let closed = match nr {
+                SysNo::Close => first,
+                SysNo::Dup2 | SysNo::Dup3 if ret != -1 => second,
+                _ => None,
+            };
+            for alias in closed.map_or(Vec::new(), |fd| self.fd_aliases_before(fd, j)) {
+                if self.active_fds.contains(&alias) {
+                    self.try_remove_fd(alias)?;
+                }
+            }
+            let unmapped = match nr {
+                SysNo::Munmap | SysNo::Mremap => first,
+                _ => None,
+            };
+            if let Some(base) = unmapped.filter(|x| self.active_map_base_idx.contains(x)) {
+                self.try_mark_base_unmapped(base)?;
+            }
+            match nr {
+                SysNo::Mmap | SysNo::Mremap if ret != -1 => {
+                    self.try_get_variable(ret)?;
+                    self.variables[ret as usize].kind = FileType::Mmap;
+                    let len = if nr == SysNo::Mmap {
+                        map_len
+                    } else {
+                        remap_len
+                    };
+                    self.add_map_base(ret, len);
+                }
+                _ if ret != -1 && self.try_get_variable(ret)?.kind.is_fd_kind() => {
+                    self.try_add_fd(ret)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // swap the syscalls at a and b unless that uses a variable before it is assigned
+    pub fn swap_syscalls(&mut self, a: usize, b: usize) -> Result<(), HdError> {
+        for pos in [a, b] {
+            if pos >= self.syscalls.len() {
+                return Err(HdError::NoSyscall(pos));
+            }
+        }
+        self.syscalls.swap(a, b);
+        if let Err(e) = self.check_def_order() {
+            self.syscalls.swap(a, b);
+            return Err(e);
+        }
+        if let Some(index) = self.copy_to_unmapped() {
+            self.syscalls.swap(a, b);
+            return Err(HdError::DanglingIndex(index));
+        }
+        self.sync_state()
+    }
+
+    // bytes the buffer passed as arg can hold, None for NULL
+    pub fn buffer_capacity(&self, arg: &Arg) -> Option<i64> {
+        let index = arg_var(Some(arg))?;
+        match &*self.variables.get(index as usize)?.var_type {
+            VariableType::UCharPtr(_, size) => Some(*size as i64),
+            VariableType::Str(s) => Some(s.len() as i64 + 1),
+            VariableType::MmapBase => self.mapping_length(index),
+            _ => None,
+        }
+    }
+
+    // length the mapping at index was created with
+    fn mapping_length(&self, index: i64) -> Option<i64> {
+        let s = &self.syscalls[self.definition(index)?];
+        match s.nr {
+            SysNo::Mmap => s.args.get(1)?.value,
+            SysNo::Mremap => s.args.get(2)?.value,
+            _ => self.map_size(index),
+        }
+    }
+
+    // the latest variable assigned before syscall pos that fits every use
+    pub(crate) fn substitute(&self, index: i64, pos: usize, uses: &[usize]) -> Option<i64> {
+        let s = &self.syscalls[pos];
+        // the offset was checked against this particular mapping
+        if s.nr == SysNo::MemcpyToMap {
+            return None;
+        }
+        let sig = signature(s.nr);
+        self.syscalls[..pos]
+            .iter()
+            .rev()
+            .map(|x| x.ret_index)
+            .filter(|x| *x != -1 && *x != index)
+            .find(|x| {
+                let arg = Arg::new(*x, true);
+                uses.iter().all(|k| {
+                    let fits = sig
+                        .get(*k)
+                        .is_some_and(|kind| arg_matches(self, *kind, &arg));
+                    // a buffer must still hold the length passed after it
+                    let length = match (sig.get(*k), sig.get(k + 1)) {
+                        (Some(ArgKind::Buffer), Some(ArgKind::Length)) => {
+                            s.args.get(k + 1).and_then(|a| a.value)
+                        }
+                        _ => None,
+                    };
+                    fits && length
+                        .is_none_or(|n| self.buffer_capacity(&arg).is_some_and(|cap| cap >= n))
+                })
+            })
+    }
+}
+
+// whether the literal at pos of nr may be replaced by an arbitrary integer
+fn mutable_arg(nr: SysNo, pos: usize, kind: ArgKind) -> bool {
+    match (nr, pos) {
+        // the mapping length bounds later memcpy_to_map
+        (SysNo::Mmap, 1) | (SysNo::Mremap, 2) | (SysNo::MemcpyToMap, _) => false,
+        _ => matches!(
+            kind,
+            ArgKind::Length
+                | ArgKind::Offset
+                | ArgKind::Flags
+                | ArgKind::FallocMode
+                | ArgKind::Mode
+                | ArgKind::Uid
+                | ArgKind::Gid
+        ),
+    }
+}
+
+// random edits of a Program that keep it valid
+pub struct Mutator {
+    rng: StdRng,
+    generator: ProgramGenerator,
+}
+
+impl Mutator {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let generator = ProgramGenerator::new(rng.gen());
+        Self { rng, generator }
+    }
+
+    // the generator inserted syscalls come from, e.g. to change its weights
+    pub fn generator(&mut self) -> &mut ProgramGenerator {
+        &mut self.generator
+    }
+
+    // apply one random mutation
+    pub fn mutate(&mut self, prog: &mut Program) -> Result<Mutation, HdError> {
+        let mut last = HdError::Empty("mutations");
+        for _ in 0..16 {
+            let result = match self.rng.gen_range(0..10) {
+                0..=2 => {
+                    let pos = self.rng.gen_range(0..=prog.syscalls.len());
+                    self.insert(prog, pos)
+                }
+                3..=4 => self.delete(prog),
+                5 => self.swap(prog),
+                6..=8 => self.mutate_arg(prog),
+                _ => self.splice_buffer(prog),
+            };
+            match result {
+                Ok(m) => return Ok(m),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+
+    // insert a generated syscall at pos
+    pub fn insert(&mut self, prog: &mut Program, pos: usize) -> Result<Mutation, HdError> {
+        if pos > prog.syscalls.len() {
+            return Err(HdError::NoSyscall(pos));
+        }
+        // the syscall is generated against the program as it stands at pos
+        let mut prefix = prog.clone();
+        prefix.syscalls.truncate(pos);
+        prefix.sync_state()?;
+        let mut last = HdError::Empty("syscall choices");
+        for _ in 0..8 {
+            let mut generated = prefix.clone();
+            self.generator.extend(&mut generated, 1);
+            let s = match generated.syscalls.pop() {
+                Some(s) if generated.syscalls.len() == pos => s,
+                _ => continue,
+            };
+            let nr = s.nr;
+            let mut candidate = prog.clone();
+            // the generator only appends variables
+            candidate
+                .variables
+                .extend_from_slice(&generated.variables[prog.variables.len()..]);
+            candidate.syscalls.insert(pos, s);
+            if let Err(e) = candidate.check_def_order() {
+                last = e;
+                continue;
+            }
+            if let Some(index) = candidate.copy_to_unmapped() {
+                last = HdError::DanglingIndex(index);
+                continue;
+            }
+            candidate.sync_state()?;
+            *prog = candidate;
+            return Ok(Mutation::Insert { pos, nr });
+        }
+        Err(last)
+    }
+
+    // delete a random syscall and repair the uses of what it assigned
+    pub fn delete(&mut self, prog: &mut Program) -> Result<Mutation, HdError> {
+        if prog.syscalls.is_empty() {
+            return Err(HdError::Empty("syscalls"));
+        }
+        let pos = self.rng.gen_range(0..prog.syscalls.len());
+        let nr = prog.syscalls[pos].nr;
+        let removed = prog.delete_syscall(pos)?;
+        Ok(Mutation::Delete { pos, nr, removed })
+    }
+
+    // swap two random syscalls
+    pub fn swap(&mut self, prog: &mut Program) -> Result<Mutation, HdError> {
+        let len = prog.syscalls.len();
+        if len < 2 {
+            return Err(HdError::Empty("syscalls"));
+        }
+        let a = self.rng.gen_range(0..len);
+        // neighbours are much more likely to be independent
+        let b = match self.rng.gen_bool(0.7) {
+            true if a + 1 < len => a + 1,
+            true => a - 1,
+            false => self.rng.gen_range(0..len),
+        };
+        if a == b {
+            return Err(HdError::NoSyscall(b));
+        }
+        prog.swap_syscalls(a, b)?;
+        Ok(Mutation::Swap { a, b })
+    }
+
+    // replace a literal integer argument with a boundary value, a nearby
+    // value or the same value with one bit flipped
+    pub fn mutate_arg(&mut self, prog: &mut Program) -> Result<Mutation, HdError> {
+        let candidates: Vec<(usize, usize)> = prog
+            .syscalls
+            .iter()
+            .enumerate()
+            .flat_map(|(i, s)| {
+                let sig = signature(s.nr);
+                s.args
+                    .iter()
+                    .enumerate()
+                    .filter(move |(j, x)| {
+                        !x.is_variable
+                            && sig.get(*j).is_some_and(|kind| mutable_arg(s.nr, *j, *kind))
+                    })
+                    .map(move |(j, _)| (i, j))
+            })
+            .collect();
+        let (i, j) = *candidates
+            .choose(&mut self.rng)
+            .ok_or(HdError::Empty("integer arguments"))?;
+        let s = &prog.syscalls[i];
+        let kind = signature(s.nr)[j];
+        let old = s.args[j].value.ok_or(HdError::MalformedArg(j))?;
+        let mut new = match self.rng.gen_range(0..3) {
+            0 => *INTERESTING_VALUES.choose(&mut self.rng).unwrap(),
+            1 => old.wrapping_add(self.rng.gen_range(-16..=16)),
+            _ => {
+                let bits = match kind {
+                    ArgKind::Flags | ArgKind::FallocMode | ArgKind::Mode => 32,
+                    _ => 64,
+                };
+                old ^ (1 << self.rng.gen_range(0..bits))
+            }
+        };
+        // a length after a buffer must not let the kernel write past it
+        if kind == ArgKind::Length && j > 0 && signature(s.nr)[j - 1] == ArgKind::Buffer {
+            if let Some(cap) = prog.buffer_capacity(&s.args[j - 1]) {
+                new = new.clamp(0, cap);
+            }
+        }
+        prog.syscalls[i].args[j] = Arg::new(new, false);
+        prog.sync_state()?;
+        Ok(Mutation::Arg {
+            syscall: i,
+            arg: j,
+            old,
+            new,
+        })
+    }
+
+    // a random prefix of a followed by a random suffix of b
+    pub fn crossover(&mut self, a: &Program, b: &Program) -> Result<Program, HdError> {
+        let cut = self.rng.gen_range(0..=a.syscalls.len());
+        let start = self.rng.gen_range(0..=b.syscalls.len());
+        a.crossover(cut, b, start)
+    }
+
+    // overwrite a range of a byte buffer with bytes of another buffer or
+    // random ones, keeping its size
+    pub fn splice_buffer(&mut self, prog: &mut Program) -> Result<Mutation, HdError> {
+        let buffers: Vec<i64> = prog
+            .variables
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| matches!(*v.var_type, VariableType::UCharPtr(_, size) if size > 0))
+            .map(|(i, _)| i as i64)
+            .collect();
+        let index = *buffers
+            .choose(&mut self.rng)
+            .ok_or(HdError::Empty("byte buffers"))?;
+        let donors: Vec<Vec<u8>> = buffers
+            .iter()
+            .filter(|x| **x != index)
+            .filter_map(|x| match &*prog.variables[*x as usize].var_type {
+                VariableType::UCharPtr(Some(v), _) if !v.is_empty() => Some(v.clone()),
+                _ => None,
+            })
+            .collect();
+        let chunk = match donors.choose(&mut self.rng) {
+            Some(donor) if self.rng.gen_bool(0.5) => {
+                let start = self.rng.gen_range(0..donor.len());
+                let end = self.rng.gen_range(start + 1..=donor.len());
+                donor[start..end].to_vec()
+            }
+            _ => {
+                let mut bytes = vec![0u8; self.rng.gen_range(1..=64)];
+                self.rng.fill(&mut bytes[..]);
+                bytes
+            }
+        };
+        let var = &mut prog.variables[index as usize];
+        let (mut bytes, size) = match &*var.var_type {
+            VariableType::UCharPtr(v, size) => (v.clone().unwrap_or_default(), *size),
+            _ => return Err(HdError::DanglingIndex(index)),
+        };
+        let offset = self.rng.gen_range(0..=bytes.len());
+        let removed = self
+            .rng
+            .gen_range(0..=(bytes.len() - offset).min(chunk.len() * 2));
+        bytes.splice(offset..offset + removed, chunk.iter().copied());
+        bytes.truncate(size as usize);
+        let inserted = bytes.len().saturating_sub(offset).min(chunk.len());
+        *var.var_type = VariableType::UCharPtr(Some(bytes), size);
+        prog.sync_xattrs();
+        Ok(Mutation::Splice {
+            index,
+            offset,
+            removed,
+            inserted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the state a program rebuilt from its syscalls alone would have
+    fn assert_in_sync(prog: &Program, what: &str) {
+        assert_eq!(prog.validate(), vec![], "{}", what);
+        let mut fresh = prog.clone();
+        fresh.sync_active().unwrap();
+        assert_eq!(prog.active_fds, fresh.active_fds, "{}", what);
+        assert_eq!(prog.active_file_fds, fresh.active_file_fds, "{}", what);
+        assert_eq!(prog.active_dir_fds, fresh.active_dir_fds, "{}", what);
+        assert_eq!(
+            prog.active_map_base_idx, fresh.active_map_base_idx,
+            "{}",
+            what
+        );
+        let mut model = FsModel::new();
+        model.run(prog);
+        if !model.diverged {
+            let mut paths: Vec<String> = model.paths().into_iter().map(|x| x.0).collect();
+            let mut avail: Vec<String> = prog
+                .avail_files
+                .iter()
+                .map(|x| x.rel_path.clone())
+                .collect();
+            paths.sort();
+            avail.sort();
+            assert_eq!(avail, paths, "{}", what);
+        }
+    }
+
+    // apply the mutation picked by f many times over generated programs
+    fn check_mutation(f: impl Fn(&mut Mutator, &mut Program) -> Result<Mutation, HdError>) {
+        for seed in 0..25 {
+            let mut prog = ProgramGenerator::new(seed).generate(30);
+            // the builders assume every syscall succeeds, start from what
+            // the model says instead
+            prog.sync_state().unwrap();
+            let mut mutator = Mutator::new(seed);
+            for round in 0..20 {
+                if let Ok(m) = f(&mut mutator, &mut prog) {
+                    assert_in_sync(&prog, &format!("seed {} round {}: {}", seed, round, m));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn swap_updates_the_files() {
+        let mut prog = Program::new();
+        prog.mkdir("d", 0o755).unwrap();
+        prog.rmdir("d").unwrap();
+        assert!(prog.find_file("d").is_none());
+        // rmdir now fails and mkdir leaves d behind
+        prog.swap_syscalls(0, 1).unwrap();
+        assert_eq!(prog.find_file("d").map(|x| x.ftype), Some(FileType::Dir));
+    }
+
+    #[test]
+    fn insert_keeps_the_program_valid() {
+        check_mutation(|m, prog| {
+            let pos = m.rng.gen_range(0..=prog.syscalls.len());
+            m.insert(prog, pos)
+        });
+    }
+
+    #[test]
+    fn delete_keeps_the_program_valid() {
+        check_mutation(|m, prog| m.delete(prog));
+    }
+
+    #[test]
+    fn swap_keeps_the_program_valid() {
+        check_mutation(|m, prog| m.swap(prog));
+    }
+
+    #[test]
+    fn mutate_arg_keeps_the_program_valid() {
+        check_mutation(|m, prog| m.mutate_arg(prog));
+    }
+
+    #[test]
+    fn splice_buffer_keeps_the_program_valid() {
+        check_mutation(|m, prog| m.splice_buffer(prog));
+    }
+
+    #[test]
+    fn mutate_keeps_the_program_valid() {
+        check_mutation(|m, prog| m.mutate(prog));
+    }
+}
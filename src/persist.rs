@@ -0,0 +1,282 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+// state of one file or directory that must survive a crash. a field is None
+// if that part was not synced, changed since, or is not known
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistedFile {
+    // paths of the file at that point, "." for the directory the program
+    // ran in. a name is only durable if its directory lists it in entries
+    pub paths: Vec<String>,
+    pub ftype: FileType,
+    // contents of a file whose data was synced, target of a symlink
+    pub data: Option<Vec<u8>>,
+    pub mode: Option<i64>,
+    pub uid: Option<i64>,
+    pub gid: Option<i64>,
+    pub xattrs: Option<Vec<Xattr>>,
+    // names a directory whose entries were synced is sure to list, the ones
+    // that were there at the sync and still are. others may show up too
+    pub entries: Option<Vec<String>>,
+}
+
+// for each syscall of a program, the files that must be found as listed
+// after a crash right after it returned
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistOracle {
+    // None once the model lost track of the program
+    pub points: Vec<Option<Vec<PersistedFile>>>,
+}
+
+impl PersistOracle {
+    pub fn at(&self, pos: usize) -> Option<&[PersistedFile]> {
+        self.points.get(pos)?.as_deref()
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, HdError> {
+        let file = read_to_string(path)?;
+        Ok(serde_json::from_str(&file)?)
+    }
+
+    pub fn to_path<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let serialized_str = serde_json::to_string(&self)?;
+        write(path, serialized_str)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Meta {
+    mode: i64,
+    uid: Option<i64>,
+    gid: Option<i64>,
+    xattrs: Option<Vec<Xattr>>,
+}
+
+// the parts of an inode a sync made durable and that did not change since
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Synced {
+    data: Option<Vec<u8>>,
+    meta: Option<Meta>,
+    entries: Option<BTreeMap<String, usize>>,
+}
+
+impl Synced {
+    fn is_empty(&self) -> bool {
+        self.data.is_none() && self.meta.is_none() && self.entries.is_none()
+    }
+}
+
+fn data_of(inode: &Inode) -> Option<Vec<u8>> {
+    match inode.ftype {
+        // contents the model lost track of cannot be promised
+        FileType::File => inode.data.clone(),
+        FileType::Symlink => Some(inode.target.clone().into_bytes()),
+        _ => None,
+    }
+}
+
+fn meta_of(inode: &Inode) -> Meta {
+    Meta {
+        mode: inode.mode,
+        uid: inode.uid,
+        gid: inode.gid,
+        xattrs: inode.xattrs_known.then(|| inode.xattrs.clone()),
+    }
+}
+
+fn entries_of(inode: &Inode) -> Option<BTreeMap<String, usize>> {
+    (inode.ftype == FileType::Dir).then(|| inode.entries.clone())
+}
+
+// make the inode durable, only its data and entries for a data sync
+fn sync(synced: &mut BTreeMap<usize, Synced>, model: &FsModel, ino: usize, meta: bool) {
+    let inode = &model.inodes[ino];
+    let x = synced.entry(ino).or_default();
+    if let Some(data) = data_of(inode) {
+        x.data = Some(data);
+    }
+    if let Some(entries) = entries_of(inode) {
+        x.entries = Some(entries);
+    }
+    if meta {
+        x.meta = Some(meta_of(inode));
+    }
+}
+
+impl Program {
+    // what fsync, fdatasync, syncfs, writes to O_SYNC or O_DSYNC fds and
+    // msync with MS_SYNC made durable, following an FsModel. fsync of a file
+    // does not make its name durable, only fsync of the directory holding it
+    // does. an msync only counts when it covers the whole file, the oracle
+    // does not track ranges. sync_file_range is left out on purpose, it
+    // writes no metadata and flushes no disk cache, so nothing it wrote is
+    // promised after a crash
+    pub fn persistence(&self) -> PersistOracle {
+        let mut model = FsModel::new();
+        model.start(self);
+        let mut synced = BTreeMap::<usize, Synced>::new();
+        let mut oracle = PersistOracle::default();
+        for s in self.syscalls.iter() {
+            let expected = model.step(self, s);
+            if model.diverged {
+                oracle.points.push(None);
+                continue;
+            }
+            if expected.errno == Some(0) {
+                let file = match s.nr {
+                    SysNo::Fsync | SysNo::Fdatasync | SysNo::Write | SysNo::Pwrite => {
+                        model.open_file(s, 0).map(|x| (x.ino, x.flags))
+                    }
+                    _ => None,
+                };
+                match (s.nr, file) {
+                    (SysNo::Fsync, Some((ino, _))) => sync(&mut synced, &model, ino, true),
+                    (SysNo::Fdatasync, Some((ino, _))) => sync(&mut synced, &model, ino, false),
+                    (SysNo::Write | SysNo::Pwrite, Some((ino, flags))) => {
+                        if flags & O_SYNC == O_SYNC {
+                            sync(&mut synced, &model, ino, true);
+                        } else if flags & O_DSYNC != 0 {
+                            sync(&mut synced, &model, ino, false);
+                        }
+                    }
+                    (SysNo::Msync, _) => {
+                        let flags = s.args.get(2).and_then(|x| x.value).unwrap_or(0);
+                        match model.msynced_file(s) {
+                            Some(ino) if flags & MS_SYNC != 0 => {
+                                sync(&mut synced, &model, ino, false)
+                            }
+                            _ => {}
+                        }
+                    }
+                    (SysNo::Syncfs, _) => {
+                        sync(&mut synced, &model, 0, true);
+                        for (_, ino) in model.paths() {
+                            sync(&mut synced, &model, ino, true);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // a part that changed after its sync may be found either way
+            for (ino, x) in synced.iter_mut() {
+                let inode = &model.inodes[*ino];
+                if x.data.is_some() && x.data != data_of(inode) {
+                    x.data = None;
+                }
+                if x.meta.as_ref().is_some_and(|m| *m != meta_of(inode)) {
+                    x.meta = None;
+                }
+                // names added since may or may not be found, removed or
+                // replaced ones were durable before and may come back
+                if let (Some(entries), Some(now)) = (&mut x.entries, entries_of(inode)) {
+                    entries.retain(|name, ino| now.get(name) == Some(ino));
+                }
+            }
+            synced.retain(|_, x| !x.is_empty());
+            oracle.points.push(Some(persisted(&model, &synced)));
+        }
+        oracle
+    }
+}
+
+fn persisted(model: &FsModel, synced: &BTreeMap<usize, Synced>) -> Vec<PersistedFile> {
+    let mut paths = BTreeMap::<usize, Vec<String>>::new();
+    paths.insert(0, vec![String::from(".")]);
+    for (path, ino) in model.paths() {
+        paths.entry(ino).or_default().push(path);
+    }
+    let mut files = Vec::<PersistedFile>::new();
+    for (ino, x) in synced.iter() {
+        // an unlinked file cannot be looked for after the crash
+        let paths = match paths.get(ino) {
+            Some(p) => p.clone(),
+            None => continue,
+        };
+        let meta = x.meta.clone();
+        files.push(PersistedFile {
+            paths,
+            ftype: model.inodes[*ino].ftype,
+            data: x.data.clone(),
+            mode: meta.as_ref().map(|m| m.mode),
+            uid: meta.as_ref().and_then(|m| m.uid),
+            gid: meta.as_ref().and_then(|m| m.gid),
+            xattrs: meta.and_then(|m| m.xattrs),
+            entries: x.entries.as_ref().map(|e| e.keys().cloned().collect()),
+        });
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(files: &'a [PersistedFile], path: &str) -> Option<&'a PersistedFile> {
+        files.iter().find(|x| x.paths.iter().any(|p| p == path))
+    }
+
+    #[test]
+    fn fsync_makes_data_durable_and_the_directory_the_name() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.write(fd, Program::SRC8192, 100).unwrap();
+        prog.fsync(fd).unwrap();
+        let dir = prog.open(".", O_RDONLY | O_DIRECTORY, 0).unwrap();
+        prog.fsync(dir).unwrap();
+        prog.unlink("f").unwrap();
+        let oracle = prog.persistence();
+        assert_eq!(oracle.at(1), Some(&[][..]));
+        let files = oracle.at(2).unwrap();
+        let f = find(files, "f").unwrap();
+        assert_eq!(f.data, Some(vec![0; 100]));
+        assert_eq!(f.mode, Some(0o644));
+        assert!(find(files, ".").is_none());
+        let files = oracle.at(4).unwrap();
+        assert_eq!(
+            find(files, ".").unwrap().entries,
+            Some(vec![String::from("f")])
+        );
+        // the name may or may not be gone after the unlink
+        let files = oracle.at(5).unwrap();
+        assert_eq!(find(files, ".").unwrap().entries, Some(vec![]));
+        assert!(find(files, "f").is_none());
+    }
+
+    #[test]
+    fn msync_of_the_whole_file_makes_its_data_durable() {
+        let mut prog = Program::new();
+        prog.prepare_buffers();
+        let ones = VariableType::UCharPtr(Some(vec![1; 10]), Program::PAGE_SIZE * 2);
+        *prog.variables[Program::SRC8192 as usize].var_type = ones;
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.write(fd, Program::DEST8192, 100).unwrap();
+        let size = Program::PAGE_SIZE as i64;
+        let base = prog
+            .mmap(fd, size, PROT_READ | PROT_WRITE, MAP_SHARED, 0)
+            .unwrap();
+        prog.memcpy_to_map(base, 0, Program::SRC8192, 10).unwrap();
+        prog.msync(base, size, MS_ASYNC).unwrap();
+        prog.sync_file_range(
+            fd,
+            0,
+            100,
+            SYNC_FILE_RANGE_WRITE | SYNC_FILE_RANGE_WAIT_AFTER,
+        )
+        .unwrap();
+        prog.msync(base, size, MS_SYNC).unwrap();
+        let oracle = prog.persistence();
+        for pos in 0..6 {
+            assert_eq!(oracle.at(pos), Some(&[][..]), "syscall {}", pos);
+        }
+        let files = oracle.at(6).unwrap();
+        let mut data = vec![0; 100];
+        data[..10].fill(1);
+        assert_eq!(find(files, "f").unwrap().data, Some(data));
+        // only data, like fdatasync
+        assert_eq!(find(files, "f").unwrap().mode, None);
+    }
+}
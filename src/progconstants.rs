@@ -347,6 +347,11 @@ pub const DEFAULT_XATTR: &[&str] = &[
     "security.capability",
 ];
 
+// setxattr flags
+pub const XATTR_CREATE: i64 = 1;
+pub const XATTR_REPLACE: i64 = 2;
+pub const XATTR_FLAGS: &[i64] = &[0, XATTR_CREATE, XATTR_REPLACE];
+
 pub const DEFAULT_XATTR_PREFIX: &[&str] = &[
     "system.",
     "trusted.",
@@ -356,3 +361,157 @@ pub const DEFAULT_XATTR_PREFIX: &[&str] = &[
     // "osx.",
     // "os2."
 ];
+
+// dirfd value that makes the *at family resolve relative to the cwd
+pub const AT_FDCWD: i64 = -100;
+pub const AT_SYMLINK_NOFOLLOW: i64 = 0x100;
+pub const AT_REMOVEDIR: i64 = 0x200;
+pub const AT_SYMLINK_FOLLOW: i64 = 0x400;
+pub const AT_NO_AUTOMOUNT: i64 = 0x800;
+pub const AT_STATX_SYNC_TYPE: i64 = 0x6000;
+pub const AT_EMPTY_PATH: i64 = 0x1000;
+
+// utimensat tv_nsec values
+pub const UTIME_NOW: i64 = (1 << 30) - 1;
+pub const UTIME_OMIT: i64 = (1 << 30) - 2;
+
+// file type bits of a mode
+pub const S_IFMT: i64 = 0o170000;
+pub const S_IFSOCK: i64 = 0o140000;
+pub const S_IFLNK: i64 = 0o120000;
+pub const S_IFREG: i64 = 0o100000;
+pub const S_IFBLK: i64 = 0o060000;
+pub const S_IFDIR: i64 = 0o040000;
+pub const S_IFCHR: i64 = 0o020000;
+pub const S_IFIFO: i64 = 0o010000;
+
+// mode bits beyond rwx
+pub const S_ISUID: i64 = 0o4000;
+pub const S_ISGID: i64 = 0o2000;
+pub const S_ISVTX: i64 = 0o1000;
+
+// renameat2 flags
+pub const RENAME_NOREPLACE: i64 = 1;
+pub const RENAME_EXCHANGE: i64 = 2;
+pub const RENAME_WHITEOUT: i64 = 4;
+
+// open flags
+pub const O_RDONLY: i64 = 0o0;
+pub const O_WRONLY: i64 = 0o1;
+pub const O_RDWR: i64 = 0o2;
+pub const O_CREAT: i64 = 0o100;
+pub const O_EXCL: i64 = 0o200;
+pub const O_TRUNC: i64 = 0o1000;
+pub const O_APPEND: i64 = 0o2000;
+pub const O_NONBLOCK: i64 = 0o4000;
+pub const O_DSYNC: i64 = 0o10000;
+pub const O_DIRECT: i64 = 0o40000;
+pub const O_DIRECTORY: i64 = 0o200000;
+pub const O_NOFOLLOW: i64 = 0o400000;
+pub const O_SYNC: i64 = 0o4010000;
+pub const O_CLOEXEC: i64 = 0o2000000;
+pub const O_PATH: i64 = 0o10000000;
+pub const O_TMPFILE: i64 = 0o20200000;
+
+// lseek whence
+pub const SEEK_SET: i64 = 0;
+pub const SEEK_CUR: i64 = 1;
+pub const SEEK_END: i64 = 2;
+
+// mmap protection and flags
+pub const PROT_NONE: i64 = 0;
+pub const PROT_READ: i64 = 1;
+pub const PROT_WRITE: i64 = 2;
+pub const PROT_EXEC: i64 = 4;
+pub const MAP_SHARED: i64 = 0x1;
+pub const MAP_PRIVATE: i64 = 0x2;
+pub const MAP_SHARED_VALIDATE: i64 = 0x3;
+pub const MAP_FIXED: i64 = 0x10;
+pub const MAP_ANONYMOUS: i64 = 0x20;
+pub const MAP_POPULATE: i64 = 0x8000;
+pub const MAP_HUGETLB: i64 = 0x40000;
+
+// msync flags
+pub const MS_ASYNC: i64 = 1;
+pub const MS_INVALIDATE: i64 = 2;
+pub const MS_SYNC: i64 = 4;
+
+// mremap flags
+pub const MREMAP_MAYMOVE: i64 = 1;
+pub const MREMAP_FIXED: i64 = 2;
+
+// madvise advice
+pub const MADV_NORMAL: i64 = 0;
+pub const MADV_RANDOM: i64 = 1;
+pub const MADV_SEQUENTIAL: i64 = 2;
+pub const MADV_WILLNEED: i64 = 3;
+pub const MADV_DONTNEED: i64 = 4;
+pub const MADV_REMOVE: i64 = 9;
+pub const MADV_COLD: i64 = 20;
+pub const MADV_PAGEOUT: i64 = 21;
+
+// fallocate modes
+pub const FALLOC_FL_KEEP_SIZE: i64 = 0x01;
+pub const FALLOC_FL_PUNCH_HOLE: i64 = 0x02;
+pub const FALLOC_FL_NO_HIDE_STALE: i64 = 0x04;
+pub const FALLOC_FL_COLLAPSE_RANGE: i64 = 0x08;
+pub const FALLOC_FL_ZERO_RANGE: i64 = 0x10;
+pub const FALLOC_FL_INSERT_RANGE: i64 = 0x20;
+pub const FALLOC_FL_UNSHARE_RANGE: i64 = 0x40;
+pub const FALLOC_FL_WRITE_ZEROES: i64 = 0x80;
+
+// mode combinations the kernel accepts, punch hole requires keep size
+pub const FALLOC_MODES: &[i64] = &[
+    0,
+    FALLOC_FL_KEEP_SIZE,
+    FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE,
+    FALLOC_FL_COLLAPSE_RANGE,
+    FALLOC_FL_ZERO_RANGE,
+    FALLOC_FL_ZERO_RANGE | FALLOC_FL_KEEP_SIZE,
+    FALLOC_FL_INSERT_RANGE,
+    FALLOC_FL_UNSHARE_RANGE,
+    FALLOC_FL_UNSHARE_RANGE | FALLOC_FL_KEEP_SIZE,
+];
+
+// sync_file_range flags
+pub const SYNC_FILE_RANGE_WAIT_BEFORE: i64 = 1;
+pub const SYNC_FILE_RANGE_WRITE: i64 = 2;
+pub const SYNC_FILE_RANGE_WAIT_AFTER: i64 = 4;
+
+// fadvise64 advice
+pub const POSIX_FADV_NORMAL: i64 = 0;
+pub const POSIX_FADV_RANDOM: i64 = 1;
+pub const POSIX_FADV_SEQUENTIAL: i64 = 2;
+pub const POSIX_FADV_WILLNEED: i64 = 3;
+pub const POSIX_FADV_DONTNEED: i64 = 4;
+pub const POSIX_FADV_NOREUSE: i64 = 5;
+
+// access modes
+pub const F_OK: i64 = 0;
+pub const X_OK: i64 = 1;
+pub const W_OK: i64 = 2;
+pub const R_OK: i64 = 4;
+
+// errno values
+pub const EPERM: i64 = 1;
+pub const ENOENT: i64 = 2;
+pub const ENXIO: i64 = 6;
+pub const E2BIG: i64 = 7;
+pub const EBADF: i64 = 9;
+pub const ENOMEM: i64 = 12;
+pub const EACCES: i64 = 13;
+pub const EFAULT: i64 = 14;
+pub const EEXIST: i64 = 17;
+pub const ENODEV: i64 = 19;
+pub const ENOTDIR: i64 = 20;
+pub const EISDIR: i64 = 21;
+pub const EINVAL: i64 = 22;
+pub const EFBIG: i64 = 27;
+pub const ESPIPE: i64 = 29;
+pub const ERANGE: i64 = 34;
+pub const ENAMETOOLONG: i64 = 36;
+pub const ENOTEMPTY: i64 = 39;
+pub const ELOOP: i64 = 40;
+pub const ENODATA: i64 = 61;
+pub const EOVERFLOW: i64 = 75;
+pub const EOPNOTSUPP: i64 = 95;
@@ -0,0 +1,146 @@
+use crate::*;
+
+// an argument in the log, paths are strings and everything else a number
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LogArg {
+    Value(i64),
+    Str(String),
+}
+
+// one line written by a program emitted with COptions::log
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LogEntry {
+    // position of the syscall in Program::syscalls
+    pub index: usize,
+    pub name: String,
+    pub args: Vec<LogArg>,
+    pub ret: i64,
+    pub errno: i64,
+}
+
+impl LogEntry {
+    pub fn is_error(&self) -> bool {
+        self.ret == -1 && self.errno != 0
+    }
+}
+
+// the entries of a log in the order they were written. lines that are not
+// json objects, e.g. mismatch reports sharing stderr, are skipped, and so is
+// a last line cut short by a crash
+pub fn parse_log(log: &str) -> Result<Vec<LogEntry>, HdError> {
+    let lines: Vec<&str> = log
+        .lines()
+        .filter(|x| x.trim_start().starts_with('{'))
+        .collect();
+    let mut entries = Vec::<LogEntry>::with_capacity(lines.len());
+    for (k, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) if k + 1 == lines.len() && !log.ends_with('\n') => break,
+            Err(e) => return Err(HdError::Json(e)),
+        }
+    }
+    Ok(entries)
+}
+
+impl Program {
+    // the log entry of each syscall, None for the syscalls that did not run
+    // or are not logged. the entries must come from this program
+    pub fn match_log(&self, entries: &[LogEntry]) -> Result<Vec<Option<LogEntry>>, HdError> {
+        let mut matched = vec![None; self.syscalls.len()];
+        for entry in entries {
+            let s = self
+                .syscalls
+                .get(entry.index)
+                .ok_or(HdError::NoSyscall(entry.index))?;
+            let name = num_to_name(s.nr);
+            if name.trim_start_matches("SYS_") != entry.name || s.args.len() != entry.args.len() {
+                return Err(HdError::LogMismatch(entry.index));
+            }
+            matched[entry.index] = Some(entry.clone());
+        }
+        Ok(matched)
+    }
+
+    // parse_log and match_log in one go
+    pub fn try_read_log(&self, log: &str) -> Result<Vec<Option<LogEntry>>, HdError> {
+        self.match_log(&parse_log(log)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        let mut prog = Program::new();
+        let fd = prog.open("f", O_CREAT | O_RDWR, 0o644).unwrap();
+        prog.close(fd).unwrap();
+        prog
+    }
+
+    #[test]
+    fn log_round_trip() {
+        let prog = program();
+        let log = concat!(
+            "{\"index\":0,\"name\":\"open\",\"args\":[\"f\",66,420],\"ret\":3,\"errno\":0}\n",
+            "mismatch index=1 expected_ret=0 expected_errno=0 ret=-1 errno=9\n",
+            "{\"index\":1,\"name\":\"close\",\"args\":[3],\"ret\":-1,\"errno\":9}\n",
+        );
+        let entries = parse_log(log).unwrap();
+        assert_eq!(
+            entries[0].args,
+            vec![
+                LogArg::Str(String::from("f")),
+                LogArg::Value(66),
+                LogArg::Value(420)
+            ]
+        );
+        assert!(entries[1].is_error());
+        let matched = prog.match_log(&entries).unwrap();
+        assert_eq!(matched[0].as_ref().map(|x| x.ret), Some(3));
+        assert_eq!(matched[1].as_ref().map(|x| x.errno), Some(EBADF));
+        for entry in entries {
+            let line = serde_json::to_string(&entry).unwrap();
+            assert_eq!(parse_log(&line).unwrap(), vec![entry]);
+        }
+    }
+
+    #[test]
+    fn a_crash_cuts_the_last_line_short() {
+        let prog = program();
+        let log = "{\"index\":0,\"name\":\"open\",\"args\":[\"f\",66,420],\"ret\":3,\"errno\":0}\n{\"index\":1,\"na";
+        let matched = prog.try_read_log(log).unwrap();
+        assert!(matched[0].is_some());
+        assert!(matched[1].is_none());
+        assert!(parse_log(&format!("{}\n", log)).is_err());
+    }
+
+    #[test]
+    fn entries_of_another_program_are_refused() {
+        let prog = program();
+        let log = "{\"index\":1,\"name\":\"open\",\"args\":[\"f\",66,420],\"ret\":3,\"errno\":0}\n";
+        assert!(matches!(
+            prog.try_read_log(log),
+            Err(HdError::LogMismatch(1))
+        ));
+        let log = "{\"index\":2,\"name\":\"close\",\"args\":[3],\"ret\":0,\"errno\":0}\n";
+        assert!(matches!(prog.try_read_log(log), Err(HdError::NoSyscall(2))));
+    }
+
+    #[test]
+    fn log_files_need_absolute_paths() {
+        let prog = program();
+        let options = |path: &str| COptions {
+            log: Some(LogTarget::File(String::from(path))),
+            ..COptions::default()
+        };
+        assert!(matches!(
+            prog.try_to_c_with(&options("run.log")),
+            Err(HdError::RelativePath(_))
+        ));
+        let c = prog.try_to_c_with(&options("/tmp/run.log")).unwrap();
+        assert!(c.contains("dprintf(__log"));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::*;
+
+// what an argument of a syscall means
+#[derive(Debug, Copy, Clone, Eq, Hash, Serialize, Deserialize, PartialEq)]
+pub enum ArgKind {
+    Fd,
+    // directory fd of the *at family, AT_FDCWD is allowed as a value
+    DirFd,
+    Path,
+    Buffer,
+    Length,
+    Offset,
+    // loff_t * the kernel reads and updates, only NULL is passed so the
+    // file offset is used
+    OffsetPtr,
+    Flags,
+    // mode of fallocate, printed with the FALLOC_FL_* names
+    FallocMode,
+    Mode,
+    Uid,
+    Gid,
+    // device number built with makedev
+    Dev,
+    XattrName,
+    // number the program reports, e.g. which crash point it reached
+    Id,
+}
+
+impl fmt::Display for ArgKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let x = match self {
+            ArgKind::Fd => "fd",
+            ArgKind::DirFd => "dir fd",
+            ArgKind::Path => "path",
+            ArgKind::Buffer => "buffer",
+            ArgKind::Length => "length",
+            ArgKind::Offset => "offset",
+            ArgKind::OffsetPtr => "offset pointer",
+            ArgKind::Flags => "flags",
+            ArgKind::FallocMode => "fallocate mode",
+            ArgKind::Mode => "mode",
+            ArgKind::Uid => "uid",
+            ArgKind::Gid => "gid",
+            ArgKind::Dev => "device number",
+            ArgKind::XattrName => "xattr name",
+            ArgKind::Id => "id",
+        };
+        write!(f, "{}", x)
+    }
+}
+
+// argument kinds of each syscall, in calling order
+pub fn signature(nr: SysNo) -> &'static [ArgKind] {
+    use ArgKind::*;
+    match nr {
+        SysNo::Open => &[Path, Flags, Mode],
+        SysNo::Read | SysNo::Write => &[Fd, Buffer, Length],
+        SysNo::Lseek => &[Fd, Offset, Flags],
+        SysNo::Getdents => &[Fd, Buffer, Length],
+        SysNo::Pread | SysNo::Pwrite => &[Fd, Buffer, Length, Offset],
+        SysNo::Fstat => &[Fd, Buffer],
+        SysNo::Stat | SysNo::Lstat => &[Path, Buffer],
+        SysNo::Rename => &[Path, Path],
+        SysNo::Fsync | SysNo::Fdatasync | SysNo::Syncfs => &[Fd],
+        SysNo::Sendfile => &[Fd, Fd, OffsetPtr, Length],
+        SysNo::Access => &[Path, Mode],
+        SysNo::Ftruncate => &[Fd, Length],
+        SysNo::Truncate => &[Path, Length],
+        SysNo::Mkdir => &[Path, Mode],
+        SysNo::Rmdir | SysNo::Unlink => &[Path],
+        SysNo::Link | SysNo::Symlink => &[Path, Path],
+        SysNo::Setxattr | SysNo::Lsetxattr => &[Path, XattrName, Buffer, Length, Flags],
+        SysNo::Getxattr | SysNo::Lgetxattr => &[Path, XattrName, Buffer, Length],
+        SysNo::Removexattr | SysNo::Lremovexattr => &[Path, XattrName],
+        SysNo::Listxattr | SysNo::Llistxattr => &[Path, Buffer, Length],
+        SysNo::Fsetxattr => &[Fd, XattrName, Buffer, Length, Flags],
+        SysNo::Fgetxattr => &[Fd, XattrName, Buffer, Length],
+        SysNo::Fremovexattr => &[Fd, XattrName],
+        SysNo::Flistxattr => &[Fd, Buffer, Length],
+        SysNo::Openat => &[DirFd, Path, Flags, Mode],
+        SysNo::Mkdirat => &[DirFd, Path, Mode],
+        SysNo::Unlinkat => &[DirFd, Path, Flags],
+        SysNo::Renameat2 => &[DirFd, Path, DirFd, Path, Flags],
+        SysNo::Linkat => &[DirFd, Path, DirFd, Path, Flags],
+        SysNo::Symlinkat => &[Path, DirFd, Path],
+        SysNo::Fstatat => &[DirFd, Path, Buffer, Flags],
+        // addr, length, prot, flags, fd, offset
+        SysNo::Mmap => &[Buffer, Length, Flags, Flags, Fd, Offset],
+        SysNo::Munmap => &[Buffer, Length],
+        SysNo::Msync | SysNo::Madvise => &[Buffer, Length, Flags],
+        // old address, old size, new size, flags, new address
+        SysNo::Mremap => &[Buffer, Length, Length, Flags, Buffer],
+        // fd, mode, offset, length
+        SysNo::Fallocate => &[Fd, FallocMode, Offset, Length],
+        SysNo::CopyFileRange => &[Fd, OffsetPtr, Fd, OffsetPtr, Length, Flags],
+        SysNo::SyncFileRange | SysNo::Fadvise64 => &[Fd, Offset, Length, Flags],
+        SysNo::Readahead => &[Fd, Offset, Length],
+        SysNo::Close | SysNo::Dup => &[Fd],
+        // oldfd, newfd
+        SysNo::Dup2 => &[Fd, Fd],
+        SysNo::Dup3 => &[Fd, Fd, Flags],
+        SysNo::Chmod => &[Path, Mode],
+        SysNo::Fchmod => &[Fd, Mode],
+        // the raw syscall has no flags argument
+        SysNo::Fchmodat => &[DirFd, Path, Mode],
+        SysNo::Chown | SysNo::Lchown => &[Path, Uid, Gid],
+        SysNo::Fchown => &[Fd, Uid, Gid],
+        SysNo::Umask => &[Mode],
+        // the times pointer may be NULL
+        SysNo::Utimensat => &[DirFd, Path, Buffer, Flags],
+        // the mode carries the S_IF* file type
+        SysNo::Mknod => &[Path, Mode, Dev],
+        SysNo::Mknodat => &[DirFd, Path, Mode, Dev],
+        // mapping, offset into it, source buffer, length
+        SysNo::MemcpyToMap => &[Buffer, Offset, Buffer, Length],
+        SysNo::CrashPoint => &[Id],
+    }
+}
+
+// whether arg can be passed where kind is expected
+pub fn arg_matches(prog: &Program, kind: ArgKind, arg: &Arg) -> bool {
+    if !arg.is_variable {
+        let value = match arg.value {
+            Some(v) => v,
+            None => return false,
+        };
+        return match kind {
+            ArgKind::Fd | ArgKind::Path | ArgKind::XattrName => false,
+            ArgKind::DirFd => value == AT_FDCWD,
+            // NULL
+            ArgKind::Buffer | ArgKind::OffsetPtr => value == 0,
+            ArgKind::Length
+            | ArgKind::Offset
+            | ArgKind::Flags
+            | ArgKind::FallocMode
+            | ArgKind::Mode
+            | ArgKind::Uid
+            | ArgKind::Gid
+            | ArgKind::Dev
+            | ArgKind::Id => true,
+        };
+    }
+    let var = match arg.index.and_then(|i| prog.variables.get(i)) {
+        Some(v) => v,
+        None => return false,
+    };
+    match kind {
+        ArgKind::Fd => var.kind.is_fd_kind(),
+        ArgKind::DirFd => var.kind == FileType::Dir,
+        ArgKind::Path | ArgKind::XattrName => matches!(*var.var_type, VariableType::Str(_)),
+        ArgKind::Buffer => matches!(
+            *var.var_type,
+            VariableType::UCharPtr(_, _)
+                | VariableType::Str(_)
+                | VariableType::VoidPtr
+                | VariableType::MmapBase
+        ),
+        ArgKind::OffsetPtr => false,
+        ArgKind::Length
+        | ArgKind::Offset
+        | ArgKind::Flags
+        | ArgKind::FallocMode
+        | ArgKind::Mode
+        | ArgKind::Uid
+        | ArgKind::Gid
+        | ArgKind::Dev
+        | ArgKind::Id => matches!(*var.var_type, VariableType::Long(_)) && !var.kind.is_fd_kind(),
+    }
+}
+
+impl Syscall {
+    // check the arguments against the signature table
+    pub fn check(&self, prog: &Program) -> Result<(), HdError> {
+        let sig = signature(self.nr);
+        if sig.len() != self.args.len() {
+            return Err(HdError::ArityMismatch {
+                nr: self.nr,
+                expected: sig.len(),
+                found: self.args.len(),
+            });
+        }
+        for (pos, (kind, arg)) in sig.iter().zip(self.args.iter()).enumerate() {
+            if !arg_matches(prog, *kind, arg) {
+                return Err(HdError::ArgKindMismatch {
+                    nr: self.nr,
+                    arg: pos,
+                    expected: *kind,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+// builds a Syscall and refuses it if it does not match its signature
+#[derive(Debug, Clone)]
+pub struct SyscallBuilder {
+    syscall: Syscall,
+}
+
+impl SyscallBuilder {
+    pub fn new(nr: SysNo) -> Self {
+        Self {
+            syscall: Syscall::new(nr),
+        }
+    }
+
+    // store the return value into the variable at index
+    pub fn ret(mut self, index: i64) -> Self {
+        self.syscall.ret_index = index;
+        self
+    }
+
+    // pass the variable at index
+    pub fn var(mut self, index: i64) -> Self {
+        self.syscall.add_arg(index, true);
+        self
+    }
+
+    // pass a literal value
+    pub fn value(mut self, value: i64) -> Self {
+        self.syscall.add_arg(value, false);
+        self
+    }
+
+    // expect the syscall to return value
+    pub fn expect_ret(mut self, value: i64) -> Self {
+        self.syscall.expected_ret = Some(value);
+        self
+    }
+
+    // expect the syscall to set errno, 0 if it must succeed
+    pub fn expect_errno(mut self, errno: i64) -> Self {
+        self.syscall.expected_errno = Some(errno);
+        self
+    }
+
+    pub fn build(self, prog: &Program) -> Result<Syscall, HdError> {
+        if self.syscall.ret_index != -1 {
+            prog.try_get_variable(self.syscall.ret_index)?;
+        }
+        self.syscall.check(prog)?;
+        Ok(self.syscall)
+    }
+}
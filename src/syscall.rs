@@ -1,6 +1,6 @@
 pub use crate::arg::*;
 
-#[derive(Debug, Copy, Eq, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Copy, Eq, Hash, Serialize, Deserialize, PartialEq, Clone)]
 pub enum SysNo {
     Open,
     Read,
@@ -29,6 +29,128 @@ pub enum SysNo {
     Getxattr,
     Removexattr,
     Listxattr,
+    Lsetxattr,
+    Fsetxattr,
+    Lgetxattr,
+    Fgetxattr,
+    Llistxattr,
+    Flistxattr,
+    Lremovexattr,
+    Fremovexattr,
+    Openat,
+    Mkdirat,
+    Unlinkat,
+    Renameat2,
+    Linkat,
+    Symlinkat,
+    Fstatat,
+    Mmap,
+    Munmap,
+    Msync,
+    Mremap,
+    Madvise,
+    Fallocate,
+    CopyFileRange,
+    SyncFileRange,
+    Fadvise64,
+    Readahead,
+    Close,
+    Dup,
+    Dup2,
+    Dup3,
+    Chmod,
+    Fchmod,
+    Fchmodat,
+    Chown,
+    Fchown,
+    Lchown,
+    Umask,
+    Utimensat,
+    Mknod,
+    Mknodat,
+    // pseudo-operation: memcpy into a mapping, not a real syscall
+    MemcpyToMap,
+    // pseudo-operation: a point where the crash harness may cut the program
+    CrashPoint,
+}
+
+impl SysNo {
+    // every variant, in declaration order
+    pub const ALL: &[SysNo] = &[
+        SysNo::Open,
+        SysNo::Read,
+        SysNo::Write,
+        SysNo::Lseek,
+        SysNo::Getdents,
+        SysNo::Pread,
+        SysNo::Pwrite,
+        SysNo::Fstat,
+        SysNo::Stat,
+        SysNo::Lstat,
+        SysNo::Rename,
+        SysNo::Fsync,
+        SysNo::Fdatasync,
+        SysNo::Syncfs,
+        SysNo::Sendfile,
+        SysNo::Access,
+        SysNo::Ftruncate,
+        SysNo::Truncate,
+        SysNo::Mkdir,
+        SysNo::Rmdir,
+        SysNo::Link,
+        SysNo::Unlink,
+        SysNo::Symlink,
+        SysNo::Setxattr,
+        SysNo::Getxattr,
+        SysNo::Removexattr,
+        SysNo::Listxattr,
+        SysNo::Lsetxattr,
+        SysNo::Fsetxattr,
+        SysNo::Lgetxattr,
+        SysNo::Fgetxattr,
+        SysNo::Llistxattr,
+        SysNo::Flistxattr,
+        SysNo::Lremovexattr,
+        SysNo::Fremovexattr,
+        SysNo::Openat,
+        SysNo::Mkdirat,
+        SysNo::Unlinkat,
+        SysNo::Renameat2,
+        SysNo::Linkat,
+        SysNo::Symlinkat,
+        SysNo::Fstatat,
+        SysNo::Mmap,
+        SysNo::Munmap,
+        SysNo::Msync,
+        SysNo::Mremap,
+        SysNo::Madvise,
+        SysNo::Fallocate,
+        SysNo::CopyFileRange,
+        SysNo::SyncFileRange,
+        SysNo::Fadvise64,
+        SysNo::Readahead,
+        SysNo::Close,
+        SysNo::Dup,
+        SysNo::Dup2,
+        SysNo::Dup3,
+        SysNo::Chmod,
+        SysNo::Fchmod,
+        SysNo::Fchmodat,
+        SysNo::Chown,
+        SysNo::Fchown,
+        SysNo::Lchown,
+        SysNo::Umask,
+        SysNo::Utimensat,
+        SysNo::Mknod,
+        SysNo::Mknodat,
+        SysNo::MemcpyToMap,
+        SysNo::CrashPoint,
+    ];
+
+    // operations that are emitted as plain C rather than syscall()
+    pub fn is_pseudo(&self) -> bool {
+        matches!(self, SysNo::MemcpyToMap | SysNo::CrashPoint)
+    }
 }
 
 pub fn num_to_name(nr: SysNo) -> String {
@@ -60,6 +182,47 @@ pub fn num_to_name(nr: SysNo) -> String {
         SysNo::Getxattr => "SYS_getxattr",
         SysNo::Listxattr => "SYS_listxattr",
         SysNo::Removexattr => "SYS_removexattr",
+        SysNo::Lsetxattr => "SYS_lsetxattr",
+        SysNo::Fsetxattr => "SYS_fsetxattr",
+        SysNo::Lgetxattr => "SYS_lgetxattr",
+        SysNo::Fgetxattr => "SYS_fgetxattr",
+        SysNo::Llistxattr => "SYS_llistxattr",
+        SysNo::Flistxattr => "SYS_flistxattr",
+        SysNo::Lremovexattr => "SYS_lremovexattr",
+        SysNo::Fremovexattr => "SYS_fremovexattr",
+        SysNo::Openat => "SYS_openat",
+        SysNo::Mkdirat => "SYS_mkdirat",
+        SysNo::Unlinkat => "SYS_unlinkat",
+        SysNo::Renameat2 => "SYS_renameat2",
+        SysNo::Linkat => "SYS_linkat",
+        SysNo::Symlinkat => "SYS_symlinkat",
+        SysNo::Fstatat => "SYS_newfstatat",
+        SysNo::Mmap => "SYS_mmap",
+        SysNo::Munmap => "SYS_munmap",
+        SysNo::Msync => "SYS_msync",
+        SysNo::Mremap => "SYS_mremap",
+        SysNo::Madvise => "SYS_madvise",
+        SysNo::Fallocate => "SYS_fallocate",
+        SysNo::CopyFileRange => "SYS_copy_file_range",
+        SysNo::SyncFileRange => "SYS_sync_file_range",
+        SysNo::Fadvise64 => "SYS_fadvise64",
+        SysNo::Readahead => "SYS_readahead",
+        SysNo::Close => "SYS_close",
+        SysNo::Dup => "SYS_dup",
+        SysNo::Dup2 => "SYS_dup2",
+        SysNo::Dup3 => "SYS_dup3",
+        SysNo::Chmod => "SYS_chmod",
+        SysNo::Fchmod => "SYS_fchmod",
+        SysNo::Fchmodat => "SYS_fchmodat",
+        SysNo::Chown => "SYS_chown",
+        SysNo::Fchown => "SYS_fchown",
+        SysNo::Lchown => "SYS_lchown",
+        SysNo::Umask => "SYS_umask",
+        SysNo::Utimensat => "SYS_utimensat",
+        SysNo::Mknod => "SYS_mknod",
+        SysNo::Mknodat => "SYS_mknodat",
+        SysNo::MemcpyToMap => "memcpy",
+        SysNo::CrashPoint => "crash_point",
     };
     String::from(x)
 }
@@ -79,6 +242,12 @@ pub struct Syscall {
     // if we do not care the return value,
     // by default it is -1.
     pub ret_index: i64,
+    // the return value and errno the syscall should give, checked by the
+    // C code of Program::try_to_checked_c. None is not checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_ret: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_errno: Option<i64>,
 }
 
 impl Syscall {
@@ -87,6 +256,8 @@ impl Syscall {
             nr,
             ret_index,
             args: Vec::<Arg>::new(),
+            expected_ret: None,
+            expected_errno: None,
         }
     }
 
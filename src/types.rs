@@ -1,3 +1,4 @@
+use crate::progconstants::*;
 pub use serde::{Deserialize, Serialize};
 pub use std::ffi::CStr;
 pub use std::fmt;
@@ -21,6 +22,43 @@ pub enum FileType {
     Dir,
     Symlink,
     Fifo,
+    CharDev,
+    BlockDev,
+    Socket,
     Mmap,
     Unknown,
 }
+
+impl FileType {
+    // kinds that a file descriptor variable can have
+    pub fn is_fd_kind(&self) -> bool {
+        *self == FileType::Dir || self.is_non_dir()
+    }
+
+    // kinds of files that are not directories
+    pub fn is_non_dir(&self) -> bool {
+        matches!(
+            self,
+            FileType::File
+                | FileType::Symlink
+                | FileType::Fifo
+                | FileType::CharDev
+                | FileType::BlockDev
+                | FileType::Socket
+        )
+    }
+
+    // file type encoded in the S_IFMT bits of a mknod mode, 0 means regular
+    pub fn from_mode(mode: i64) -> Self {
+        match mode & S_IFMT {
+            0 | S_IFREG => FileType::File,
+            S_IFDIR => FileType::Dir,
+            S_IFLNK => FileType::Symlink,
+            S_IFIFO => FileType::Fifo,
+            S_IFCHR => FileType::CharDev,
+            S_IFBLK => FileType::BlockDev,
+            S_IFSOCK => FileType::Socket,
+            _ => FileType::Unknown,
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use crate::*;
+
+// a single well-formedness problem found by Program::validate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    // two variables share a C identifier
+    DuplicateVariableName {
+        index: usize,
+        name: String,
+    },
+    // the variable has no C representation
+    UnprintableVariable {
+        index: usize,
+    },
+    // argument refers past the end of variables
+    DanglingArg {
+        syscall: usize,
+        arg: usize,
+        index: i64,
+    },
+    // argument has neither a value nor an index
+    MalformedArg {
+        syscall: usize,
+        arg: usize,
+    },
+    // return value is stored in a variable that does not exist
+    DanglingRet {
+        syscall: usize,
+        index: i64,
+    },
+    // return value is stored in a variable that cannot hold it (e.g. a Str)
+    RetNotScalar {
+        syscall: usize,
+        index: i64,
+    },
+    // the syscall has a different number of arguments than its signature
+    ArityMismatch {
+        syscall: usize,
+        expected: usize,
+        found: usize,
+    },
+    // the argument does not fit the kind its signature expects
+    ArgKindMismatch {
+        syscall: usize,
+        arg: usize,
+        expected: ArgKind,
+    },
+    // an entry of an fd list that does not exist
+    DanglingFd {
+        list: &'static str,
+        index: i64,
+    },
+    // an entry of an fd list that is not a file descriptor
+    NotAnFd {
+        list: &'static str,
+        index: i64,
+        kind: FileType,
+    },
+    // an fd in active_file_fds or active_dir_fds that is missing from active_fds
+    FdNotActive {
+        list: &'static str,
+        index: i64,
+    },
+    // an entry of active_map_base_idx that does not exist
+    DanglingMapBase {
+        index: i64,
+    },
+    // an entry of active_map_base_idx that is not a mapping
+    NotAMapBase {
+        index: i64,
+        kind: FileType,
+    },
+    // active_map_base_idx and active_map_size have different lengths
+    MapSizeMismatch {
+        bases: usize,
+        sizes: usize,
+    },
+    // a file object whose path variable does not exist
+    DanglingPath {
+        list: &'static str,
+        path: String,
+        index: i64,
+    },
+    // a file object whose path variable is not a Str
+    PathNotStr {
+        list: &'static str,
+        path: String,
+        index: i64,
+    },
+    // a file object stored in the wrong avail list
+    WrongFileList {
+        list: &'static str,
+        path: String,
+        ftype: FileType,
+    },
+    // a file object in avail_dirs or avail_non_dirs that is missing from avail_files
+    FileNotAvailable {
+        list: &'static str,
+        path: String,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::DuplicateVariableName { index, name } => {
+                write!(f, "variable {} reuses the name {}", index, name)
+            }
+            Diagnostic::UnprintableVariable { index } => {
+                write!(f, "variable {} cannot be printed as C", index)
+            }
+            Diagnostic::DanglingArg {
+                syscall,
+                arg,
+                index,
+            } => write!(
+                f,
+                "syscall {} argument {} refers to missing variable {}",
+                syscall, arg, index
+            ),
+            Diagnostic::MalformedArg { syscall, arg } => write!(
+                f,
+                "syscall {} argument {} has no value or index",
+                syscall, arg
+            ),
+            Diagnostic::DanglingRet { syscall, index } => write!(
+                f,
+                "syscall {} returns into missing variable {}",
+                syscall, index
+            ),
+            Diagnostic::RetNotScalar { syscall, index } => write!(
+                f,
+                "syscall {} returns into non-scalar variable {}",
+                syscall, index
+            ),
+            Diagnostic::ArityMismatch {
+                syscall,
+                expected,
+                found,
+            } => write!(
+                f,
+                "syscall {} takes {} arguments, got {}",
+                syscall, expected, found
+            ),
+            Diagnostic::ArgKindMismatch {
+                syscall,
+                arg,
+                expected,
+            } => write!(
+                f,
+                "syscall {} argument {} must be a {}",
+                syscall, arg, expected
+            ),
+            Diagnostic::DanglingFd { list, index } => {
+                write!(f, "{} lists missing variable {}", list, index)
+            }
+            Diagnostic::NotAnFd { list, index, kind } => {
+                write!(f, "{} lists variable {} of kind {:?}", list, index, kind)
+            }
+            Diagnostic::FdNotActive { list, index } => {
+                write!(f, "{} lists variable {} not in active_fds", list, index)
+            }
+            Diagnostic::DanglingMapBase { index } => {
+                write!(f, "active_map_base_idx lists missing variable {}", index)
+            }
+            Diagnostic::NotAMapBase { index, kind } => write!(
+                f,
+                "active_map_base_idx lists variable {} of kind {:?}",
+                index, kind
+            ),
+            Diagnostic::MapSizeMismatch { bases, sizes } => {
+                write!(f, "{} map bases but {} map sizes", bases, sizes)
+            }
+            Diagnostic::DanglingPath { list, path, index } => write!(
+                f,
+                "{} entry {} refers to missing variable {}",
+                list, path, index
+            ),
+            Diagnostic::PathNotStr { list, path, index } => write!(
+                f,
+                "{} entry {} refers to non-path variable {}",
+                list, path, index
+            ),
+            Diagnostic::WrongFileList { list, path, ftype } => {
+                write!(f, "{} entry {} has type {:?}", list, path, ftype)
+            }
+            Diagnostic::FileNotAvailable { list, path } => {
+                write!(f, "{} entry {} is not in avail_files", list, path)
+            }
+        }
+    }
+}
+
+impl Program {
+    // check that all indices and lists agree with each other
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diags = Vec::<Diagnostic>::new();
+        self.validate_variables(&mut diags);
+        self.validate_syscalls(&mut diags);
+        self.validate_fds(&mut diags);
+        self.validate_map_bases(&mut diags);
+        self.validate_files(&mut diags);
+        diags
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_empty()
+    }
+
+    fn validate_variables(&self, diags: &mut Vec<Diagnostic>) {
+        let mut names = HashMap::<&str, usize>::new();
+        for (index, v) in self.variables.iter().enumerate() {
+            if names.insert(&v.name, index).is_some() {
+                diags.push(Diagnostic::DuplicateVariableName {
+                    index,
+                    name: v.name.clone(),
+                });
+            }
+            if v.try_to_c().is_err() {
+                diags.push(Diagnostic::UnprintableVariable { index });
+            }
+        }
+    }
+
+    fn validate_syscalls(&self, diags: &mut Vec<Diagnostic>) {
+        for (i, s) in self.syscalls.iter().enumerate() {
+            if s.ret_index != -1 {
+                match self.try_get_variable(s.ret_index) {
+                    Err(_) => diags.push(Diagnostic::DanglingRet {
+                        syscall: i,
+                        index: s.ret_index,
+                    }),
+                    Ok(v) => match *v.var_type {
+                        VariableType::Long(_) | VariableType::VoidPtr | VariableType::MmapBase => {}
+                        _ => diags.push(Diagnostic::RetNotScalar {
+                            syscall: i,
+                            index: s.ret_index,
+                        }),
+                    },
+                }
+            }
+            let before = diags.len();
+            for (j, arg) in s.args.iter().enumerate() {
+                if !arg.is_variable {
+                    if arg.value.is_none() {
+                        diags.push(Diagnostic::MalformedArg { syscall: i, arg: j });
+                    }
+                    continue;
+                }
+                match arg.index {
+                    None => diags.push(Diagnostic::MalformedArg { syscall: i, arg: j }),
+                    Some(index) if index >= self.variables.len() => {
+                        diags.push(Diagnostic::DanglingArg {
+                            syscall: i,
+                            arg: j,
+                            index: index as i64,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            // kinds are only meaningful once every argument resolves
+            if diags.len() != before {
+                continue;
+            }
+            match s.check(self) {
+                Err(HdError::ArityMismatch {
+                    expected, found, ..
+                }) => diags.push(Diagnostic::ArityMismatch {
+                    syscall: i,
+                    expected,
+                    found,
+                }),
+                Err(HdError::ArgKindMismatch { arg, expected, .. }) => {
+                    diags.push(Diagnostic::ArgKindMismatch {
+                        syscall: i,
+                        arg,
+                        expected,
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn validate_fds(&self, diags: &mut Vec<Diagnostic>) {
+        let lists: [(&'static str, &Vec<i64>); 3] = [
+            ("active_fds", &self.active_fds),
+            ("active_file_fds", &self.active_file_fds),
+            ("active_dir_fds", &self.active_dir_fds),
+        ];
+        for (list, fds) in lists {
+            for &index in fds.iter() {
+                let kind = match self.try_get_variable(index) {
+                    Ok(v) => v.kind,
+                    Err(_) => {
+                        diags.push(Diagnostic::DanglingFd { list, index });
+                        continue;
+                    }
+                };
+                let ok = match list {
+                    "active_file_fds" => kind.is_fd_kind() && kind != FileType::Dir,
+                    "active_dir_fds" => kind == FileType::Dir,
+                    _ => kind.is_fd_kind(),
+                };
+                if !ok {
+                    diags.push(Diagnostic::NotAnFd { list, index, kind });
+                }
+                if list != "active_fds" && !self.active_fds.contains(&index) {
+                    diags.push(Diagnostic::FdNotActive { list, index });
+                }
+            }
+        }
+    }
+
+    fn validate_map_bases(&self, diags: &mut Vec<Diagnostic>) {
+        if self.active_map_base_idx.len() != self.active_map_size.len() {
+            diags.push(Diagnostic::MapSizeMismatch {
+                bases: self.active_map_base_idx.len(),
+                sizes: self.active_map_size.len(),
+            });
+        }
+        for &index in self.active_map_base_idx.iter() {
+            match self.try_get_variable(index) {
+                Err(_) => diags.push(Diagnostic::DanglingMapBase { index }),
+                Ok(v) if v.kind != FileType::Mmap => diags.push(Diagnostic::NotAMapBase {
+                    index,
+                    kind: v.kind,
+                }),
+                _ => {}
+            }
+        }
+    }
+
+    fn validate_files(&self, diags: &mut Vec<Diagnostic>) {
+        let lists: [(&'static str, &Vec<FileObject>); 3] = [
+            ("avail_files", &self.avail_files),
+            ("avail_dirs", &self.avail_dirs),
+            ("avail_non_dirs", &self.avail_non_dirs),
+        ];
+        for (list, fobjs) in lists {
+            for fobj in fobjs.iter() {
+                let path = fobj.rel_path.clone();
+                let ok = match list {
+                    "avail_dirs" => fobj.ftype == FileType::Dir,
+                    "avail_non_dirs" => fobj.ftype.is_fd_kind() && fobj.ftype != FileType::Dir,
+                    _ => fobj.ftype.is_fd_kind(),
+                };
+                if !ok {
+                    diags.push(Diagnostic::WrongFileList {
+                        list,
+                        path: path.clone(),
+                        ftype: fobj.ftype,
+                    });
+                }
+                if list != "avail_files"
+                    && !self
+                        .avail_files
+                        .iter()
+                        .any(|x| x.rel_path == fobj.rel_path && x.ftype == fobj.ftype)
+                {
+                    diags.push(Diagnostic::FileNotAvailable {
+                        list,
+                        path: path.clone(),
+                    });
+                }
+                // only avail_files is guaranteed to carry the path variable index
+                if list != "avail_files" {
+                    continue;
+                }
+                match self.try_get_variable(fobj.fd_index) {
+                    Err(_) => diags.push(Diagnostic::DanglingPath {
+                        list,
+                        path,
+                        index: fobj.fd_index,
+                    }),
+                    Ok(v) => {
+                        if !matches!(*v.var_type, VariableType::Str(_)) {
+                            diags.push(Diagnostic::PathNotStr {
+                                list,
+                                path,
+                                index: fobj.fd_index,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
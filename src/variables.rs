@@ -1,3 +1,4 @@
+pub use crate::error::*;
 pub use crate::types::*;
 pub use std::ffi::CString;
 pub use std::fmt;
@@ -18,25 +19,23 @@ impl Variable {
     }
 
     pub fn is_pointer(self) -> bool {
-        match *self.var_type {
-            VariableType::UCharPtr(_, _) | VariableType::Str(_) | VariableType::VoidPtr => true,
-            _ => false,
-        }
+        matches!(
+            *self.var_type,
+            VariableType::UCharPtr(_, _) | VariableType::Str(_) | VariableType::VoidPtr
+        )
     }
 }
 
-impl fmt::Display for Variable {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Variable {
+    // C declaration (and initialization) of the variable
+    pub fn try_to_c(&self) -> Result<String, HdError> {
         match &*self.var_type {
-            VariableType::Long(value) => write!(f, "\tlong {} = {};\n", self.name, value),
+            VariableType::Long(value) => Ok(format!("\tlong {} = {};\n", self.name, value)),
             VariableType::Str(value) => {
-                let cstr = format!(
-                    "{:?}",
-                    CString::new(&**value).expect("invalid c string in fmt::Display Variable")
-                );
-                let mut var = String::from(&cstr[1..cstr.len() - 1]);
+                CString::new(&**value).map_err(|_| HdError::Unprintable(self.name.clone()))?;
+                let mut var = c_escape(value.as_bytes());
                 var.push_str("\\x00");
-                write!(f, "\tchar {}[] = \"{}\";\n", self.name, var)
+                Ok(format!("\tchar {}[] = \"{}\";\n", self.name, var))
             }
             VariableType::UCharPtr(value, size) => {
                 let mut line = format!("\tunsigned char {}[{}];\n", self.name, size);
@@ -47,18 +46,45 @@ impl fmt::Display for Variable {
                         line.push_str(&format!("{});\n", size));
                     }
                     Some(v) => {
-                        line.push_str(&format!(
-                            "\tmemcpy({},{:?}",
-                            self.name,
-                            CString::new(&*v.clone())
-                                .expect("invalid c string in fmt::Display Veriable UCharPtr")
-                        ));
-                        line.push_str(&format!(",{});", size));
+                        if v.len() > *size as usize {
+                            return Err(HdError::Unprintable(self.name.clone()));
+                        }
+                        if v.len() < *size as usize {
+                            line.push_str(&format!("\tmemset({}, 0,{});\n", self.name, size));
+                        }
+                        line.push_str(&format!("\tmemcpy({},\"{}\"", self.name, c_escape(v)));
+                        line.push_str(&format!(",{});", v.len()));
                     }
                 }
-                return write!(f, "{}", line);
+                Ok(line)
             }
-            _ => Err(fmt::Error),
+            VariableType::VoidPtr => Ok(format!("\tvoid *{} = NULL;\n", self.name)),
+            VariableType::MmapBase => Ok(format!("\tvoid *{} = MAP_FAILED;\n", self.name)),
+            _ => Err(HdError::Unprintable(self.name.clone())),
+        }
+    }
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let line = self.try_to_c().map_err(|_| fmt::Error)?;
+        write!(f, "{}", line)
+    }
+}
+
+// body of a C string literal holding exactly bytes; octal escapes are used
+// because hex escapes would swallow following hex digits
+pub fn c_escape(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &b in bytes {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            // avoid trigraphs
+            b'?' => out.push_str("\\?"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
         }
     }
+    out
 }
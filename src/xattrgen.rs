@@ -0,0 +1,235 @@
+use crate::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+// limits from linux/limits.h
+pub const XATTR_NAME_MAX: usize = 255;
+pub const XATTR_SIZE_MAX: usize = 65536;
+
+// posix acl xattr layout from linux/posix_acl_xattr.h
+pub const POSIX_ACL_XATTR_VERSION: u32 = 0x0002;
+pub const ACL_USER_OBJ: u16 = 0x01;
+pub const ACL_USER: u16 = 0x02;
+pub const ACL_GROUP_OBJ: u16 = 0x04;
+pub const ACL_GROUP: u16 = 0x08;
+pub const ACL_MASK: u16 = 0x10;
+pub const ACL_OTHER: u16 = 0x20;
+pub const ACL_UNDEFINED_ID: u32 = 0xffffffff;
+
+// file capability layout from linux/capability.h
+pub const VFS_CAP_REVISION_2: u32 = 0x02000000;
+pub const VFS_CAP_REVISION_3: u32 = 0x03000000;
+pub const VFS_CAP_FLAGS_EFFECTIVE: u32 = 0x000001;
+
+// namespaces the kernel does not know
+const INVALID_PREFIX: &[&str] = &["", "osx.", "os2.", "User.", "foo.", "."];
+
+fn random_suffix<R: Rng>(rng: &mut R, len: usize) -> String {
+    const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789_.-";
+    (0..len)
+        .map(|_| *CHARSET.choose(rng).unwrap() as char)
+        .collect()
+}
+
+// a known name, a known prefix with a random suffix, or an adversarial name
+pub fn gen_xattr_name<R: Rng>(rng: &mut R) -> String {
+    match rng.gen_range(0..10) {
+        0..=3 => String::from(*DEFAULT_XATTR.choose(rng).unwrap()),
+        4..=6 => {
+            let len = rng.gen_range(1..16);
+            format!(
+                "{}{}",
+                DEFAULT_XATTR_PREFIX.choose(rng).unwrap(),
+                random_suffix(rng, len)
+            )
+        }
+        // longer than XATTR_NAME_MAX, ERANGE
+        7 => {
+            let prefix = DEFAULT_XATTR_PREFIX.choose(rng).unwrap();
+            let len = rng.gen_range(XATTR_NAME_MAX + 1..XATTR_NAME_MAX + 64) - prefix.len();
+            format!("{}{}", prefix, random_suffix(rng, len))
+        }
+        // empty name or a bare prefix
+        8 => match rng.gen_bool(0.5) {
+            true => String::new(),
+            false => String::from(*DEFAULT_XATTR_PREFIX.choose(rng).unwrap()),
+        },
+        // EOPNOTSUPP
+        _ => {
+            let len = rng.gen_range(1..16);
+            format!(
+                "{}{}",
+                INVALID_PREFIX.choose(rng).unwrap(),
+                random_suffix(rng, len)
+            )
+        }
+    }
+}
+
+// a value suited to name, sometimes deliberately malformed
+pub fn gen_xattr_value<R: Rng>(rng: &mut R, name: &str) -> Vec<u8> {
+    if name.starts_with("system.posix_acl_") && rng.gen_bool(0.8) {
+        return match rng.gen_bool(0.7) {
+            true => gen_posix_acl(rng),
+            false => gen_bad_posix_acl(rng),
+        };
+    }
+    if name == "security.capability" && rng.gen_bool(0.8) {
+        return gen_capability(rng);
+    }
+    match rng.gen_range(0..10) {
+        0 => Vec::<u8>::new(),
+        1 => random_bytes(rng, XATTR_SIZE_MAX),
+        // one past the maximum, E2BIG
+        2 => random_bytes(rng, XATTR_SIZE_MAX + 1),
+        3..=5 => {
+            let len = rng.gen_range(1..256);
+            random_suffix(rng, len).into_bytes()
+        }
+        _ => {
+            let len = rng.gen_range(1..4096);
+            random_bytes(rng, len)
+        }
+    }
+}
+
+// name, value and one of XATTR_FLAGS
+pub fn gen_xattr<R: Rng>(rng: &mut R) -> Xattr {
+    let name = gen_xattr_name(rng);
+    let value = gen_xattr_value(rng, &name);
+    let flags = *XATTR_FLAGS.choose(rng).unwrap();
+    Xattr(name, value, flags)
+}
+
+fn random_bytes<R: Rng>(rng: &mut R, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rng.fill(&mut bytes[..]);
+    bytes
+}
+
+fn push_acl_entry(blob: &mut Vec<u8>, tag: u16, perm: u16, id: u32) {
+    blob.extend_from_slice(&tag.to_le_bytes());
+    blob.extend_from_slice(&perm.to_le_bytes());
+    blob.extend_from_slice(&id.to_le_bytes());
+}
+
+// entries sorted by tag and id, with a mask whenever named entries exist
+pub fn gen_posix_acl<R: Rng>(rng: &mut R) -> Vec<u8> {
+    let mut blob = POSIX_ACL_XATTR_VERSION.to_le_bytes().to_vec();
+    push_acl_entry(
+        &mut blob,
+        ACL_USER_OBJ,
+        rng.gen_range(0..8),
+        ACL_UNDEFINED_ID,
+    );
+    let mut users: Vec<u32> = (0..rng.gen_range(0..3))
+        .map(|_| rng.gen_range(0..65536))
+        .collect();
+    users.sort_unstable();
+    users.dedup();
+    for uid in users.iter() {
+        push_acl_entry(&mut blob, ACL_USER, rng.gen_range(0..8), *uid);
+    }
+    push_acl_entry(
+        &mut blob,
+        ACL_GROUP_OBJ,
+        rng.gen_range(0..8),
+        ACL_UNDEFINED_ID,
+    );
+    let mut groups: Vec<u32> = (0..rng.gen_range(0..3))
+        .map(|_| rng.gen_range(0..65536))
+        .collect();
+    groups.sort_unstable();
+    groups.dedup();
+    for gid in groups.iter() {
+        push_acl_entry(&mut blob, ACL_GROUP, rng.gen_range(0..8), *gid);
+    }
+    if !users.is_empty() || !groups.is_empty() {
+        push_acl_entry(&mut blob, ACL_MASK, rng.gen_range(0..8), ACL_UNDEFINED_ID);
+    }
+    push_acl_entry(&mut blob, ACL_OTHER, rng.gen_range(0..8), ACL_UNDEFINED_ID);
+    blob
+}
+
+// a valid acl broken in one way
+pub fn gen_bad_posix_acl<R: Rng>(rng: &mut R) -> Vec<u8> {
+    let mut blob = gen_posix_acl(rng);
+    match rng.gen_range(0..5) {
+        // unknown version
+        0 => blob[0] = rng.gen_range(3..=255),
+        // not a whole number of entries
+        1 => {
+            let cut = rng.gen_range(1..8);
+            blob.truncate(blob.len() - cut);
+        }
+        // entries out of order
+        2 => {
+            let other = blob.split_off(blob.len() - 8);
+            blob.splice(4..4, other);
+        }
+        // named user without a mask
+        3 => {
+            blob.truncate(4);
+            push_acl_entry(&mut blob, ACL_USER_OBJ, 7, ACL_UNDEFINED_ID);
+            push_acl_entry(&mut blob, ACL_USER, 7, rng.gen());
+            push_acl_entry(&mut blob, ACL_GROUP_OBJ, 7, ACL_UNDEFINED_ID);
+            push_acl_entry(&mut blob, ACL_OTHER, 7, ACL_UNDEFINED_ID);
+        }
+        // perm and tag bits out of range
+        _ => {
+            let pos = 4 + 8 * rng.gen_range(0..(blob.len() - 4) / 8);
+            blob[pos] = rng.gen();
+            blob[pos + 2] = rng.gen();
+        }
+    }
+    blob
+}
+
+// vfs_cap_data of revision 2 or 3, or a blob with a bad revision
+pub fn gen_capability<R: Rng>(rng: &mut R) -> Vec<u8> {
+    let revision = match rng.gen_range(0..5) {
+        0..=1 => VFS_CAP_REVISION_2,
+        2..=3 => VFS_CAP_REVISION_3,
+        _ => rng.gen::<u32>() & 0xff000000,
+    };
+    let mut magic = revision;
+    if rng.gen_bool(0.5) {
+        magic |= VFS_CAP_FLAGS_EFFECTIVE;
+    }
+    let mut blob = magic.to_le_bytes().to_vec();
+    // permitted and inheritable, low then high words
+    for _ in 0..4 {
+        blob.extend_from_slice(&rng.gen::<u32>().to_le_bytes());
+    }
+    if revision == VFS_CAP_REVISION_3 {
+        let rootid: u32 = rng.gen_range(0..65536);
+        blob.extend_from_slice(&rootid.to_le_bytes());
+    }
+    blob
+}
+
+// seeded source of xattrs for callers without their own rng
+pub struct XattrGenerator {
+    rng: StdRng,
+}
+
+impl XattrGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn name(&mut self) -> String {
+        gen_xattr_name(&mut self.rng)
+    }
+
+    pub fn value(&mut self, name: &str) -> Vec<u8> {
+        gen_xattr_value(&mut self.rng, name)
+    }
+
+    pub fn xattr(&mut self) -> Xattr {
+        gen_xattr(&mut self.rng)
+    }
+}
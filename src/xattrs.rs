@@ -0,0 +1,129 @@
+use crate::*;
+
+impl SysNo {
+    // syscalls that change the xattr set of a file
+    pub fn changes_xattrs(&self) -> bool {
+        matches!(
+            self,
+            SysNo::Setxattr
+                | SysNo::Lsetxattr
+                | SysNo::Fsetxattr
+                | SysNo::Removexattr
+                | SysNo::Lremovexattr
+                | SysNo::Fremovexattr
+        )
+    }
+}
+
+impl Program {
+    // names of the xattrs expected on the file at path
+    pub fn xattr_names(&self, path: &str) -> Vec<String> {
+        match self.find_file(path) {
+            Some(fobj) => fobj.xattrs.iter().map(|x| x.0.clone()).collect(),
+            None => Vec::<String>::new(),
+        }
+    }
+
+    // files expected to carry at least one xattr
+    pub fn files_with_xattrs(&self) -> Vec<&FileObject> {
+        self.avail_files
+            .iter()
+            .filter(|x| !x.xattrs.is_empty())
+            .collect()
+    }
+
+    // rebuild the xattrs of every file object from its initial xattrs and
+    // the syscalls
+    pub fn sync_xattrs(&mut self) {
+        let lists = [
+            &mut self.avail_files,
+            &mut self.avail_dirs,
+            &mut self.avail_non_dirs,
+        ];
+        for list in lists {
+            for fobj in list.iter_mut() {
+                let initial = fobj
+                    .initial_xattrs
+                    .get_or_insert_with(|| fobj.xattrs.clone());
+                fobj.xattrs = initial.clone();
+            }
+        }
+        for pos in 0..self.syscalls.len() {
+            self.apply_xattr_syscall(pos);
+        }
+    }
+
+    // update the file objects with the effect of the xattr syscall at pos
+    pub fn apply_xattr_syscall(&mut self, pos: usize) {
+        let s = match self.syscalls.get(pos) {
+            Some(s) if s.nr.changes_xattrs() => s.clone(),
+            _ => return,
+        };
+        let (path, follow) = match s.nr {
+            SysNo::Setxattr | SysNo::Removexattr => (self.arg_str(s.args.first()), true),
+            SysNo::Lsetxattr | SysNo::Lremovexattr => (self.arg_str(s.args.first()), false),
+            _ => (
+                arg_var(s.args.first()).and_then(|fd| self.fd_path_before(fd, pos)),
+                true,
+            ),
+        };
+        let path = match path.and_then(|p| self.path_after(&p, pos)) {
+            Some(p) => p,
+            None => return,
+        };
+        // the target of a followed symlink is not tracked
+        if follow && self.find_file(&path).map(|x| x.ftype) == Some(FileType::Symlink) {
+            return;
+        }
+        let name = match self.arg_str(s.args.get(1)) {
+            Some(n) => n,
+            None => return,
+        };
+        let change = match s.nr {
+            SysNo::Setxattr | SysNo::Lsetxattr | SysNo::Fsetxattr => {
+                let value = self.arg_bytes(s.args.get(2), s.args.get(3));
+                let flags = s.args.get(4).and_then(|x| x.value).unwrap_or(0);
+                Some(Xattr(name.clone(), value, flags))
+            }
+            _ => None,
+        };
+        let lists = [
+            &mut self.avail_files,
+            &mut self.avail_dirs,
+            &mut self.avail_non_dirs,
+        ];
+        for list in lists {
+            for fobj in list.iter_mut().filter(|x| x.rel_path == path) {
+                fobj.initial_xattrs
+                    .get_or_insert_with(|| fobj.xattrs.clone());
+                let existing = fobj.xattrs.iter().position(|x| x.0 == name);
+                match (&change, existing) {
+                    (None, _) => fobj.xattrs.retain(|x| x.0 != name),
+                    // fails with EEXIST
+                    (Some(x), Some(_)) if x.2 & XATTR_CREATE != 0 => {}
+                    (Some(x), Some(i)) => fobj.xattrs[i] = x.clone(),
+                    // fails with ENODATA
+                    (Some(x), None) if x.2 & XATTR_REPLACE != 0 => {}
+                    (Some(x), None) => fobj.xattrs.push(x.clone()),
+                }
+            }
+        }
+    }
+
+    // the first size bytes of the buffer passed as arg
+    fn arg_bytes(&self, arg: Option<&Arg>, size: Option<&Arg>) -> Vec<u8> {
+        let mut bytes = match arg_var(arg).and_then(|i| self.variables.get(i as usize)) {
+            Some(v) => match &*v.var_type {
+                VariableType::UCharPtr(Some(b), _) => b.clone(),
+                VariableType::UCharPtr(None, n) => vec![0; *n as usize],
+                VariableType::Str(s) => s.clone().into_bytes(),
+                _ => Vec::<u8>::new(),
+            },
+            None => Vec::<u8>::new(),
+        };
+        if let Some(n) = size.and_then(|x| x.value) {
+            bytes.resize(n.max(0) as usize, 0);
+        }
+        bytes
+    }
+}